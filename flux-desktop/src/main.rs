@@ -22,19 +22,23 @@ use winit::platform::macos::WindowBuilderExtMacOS;
 #[cfg(target_os = "windows")]
 use winit::platform::windows::WindowBuilderExtWindows;
 
+use flux::render::SafeZones;
 use flux::{Flux, Settings};
 
 // Global flag to signal quit from menu bar
 static SHOULD_QUIT: AtomicBool = AtomicBool::new(false);
 
 // Global settings for menu control
-static CURRENT_COLOR_SCHEME: AtomicU32 = AtomicU32::new(0); // 0=Original, 1=Plasma, 2=Poolside, 3=SpaceGrey
+static CURRENT_COLOR_SCHEME: AtomicU32 = AtomicU32::new(0); // 0=Original, 1=Plasma, 2=Poolside, 3=SpaceGrey, 4=Custom Image, 5=Slideshow, 6=Animated Image
 static CURRENT_DENSITY: AtomicU32 = AtomicU32::new(1); // 0=Sparse, 1=Normal, 2=Dense
 static CURRENT_NOISE_STRENGTH: AtomicU32 = AtomicU32::new(1); // 0=Low, 1=Medium, 2=High, 3=Max
 static CURRENT_LINE_LENGTH: AtomicU32 = AtomicU32::new(1); // 0=Short, 1=Medium, 2=Long, 3=Extra Long
 static CURRENT_LINE_WIDTH: AtomicU32 = AtomicU32::new(1); // 0=Thin, 1=Medium, 2=Thick
 static CURRENT_VIEW_SCALE: AtomicU32 = AtomicU32::new(1); // 0=Compact, 1=Normal, 2=Wide
 static CURRENT_BRIGHTNESS: AtomicU32 = AtomicU32::new(1); // 0=Dim, 1=Normal, 2=Bright, 3=Vivid
+static CURRENT_VIBRANCY: AtomicU32 = AtomicU32::new(0); // 0=Muted, 1=Balanced, 2=Rich, 3=Full
+static HDR_ENABLED: AtomicBool = AtomicBool::new(false);
+static DISPLAY_P3_ENABLED: AtomicBool = AtomicBool::new(false);
 static SETTINGS_CHANGED: AtomicBool = AtomicBool::new(false);
 
 // Global flag to signal screen configuration changed (resolution, refresh rate, display added/removed)
@@ -47,6 +51,20 @@ fn custom_color_wheel() -> &'static Mutex<Option<[f32; 24]>> {
     INSTANCE.get_or_init(|| Mutex::new(None))
 }
 
+// Global storage for a newly loaded slideshow or animated image
+// Written by menu handler thread, taken by render/event loop thread and injected into every renderer
+fn pending_color_animation() -> &'static Mutex<Option<flux::render::color::Animation>> {
+    static INSTANCE: OnceLock<Mutex<Option<flux::render::color::Animation>>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(None))
+}
+
+// In-memory copy of the saved preferences, kept in sync by save_preferences
+// Read by render/event loop thread so that settings changes don't go back to disk
+fn cached_preferences() -> &'static Mutex<UserPreferences> {
+    static INSTANCE: OnceLock<Mutex<UserPreferences>> = OnceLock::new();
+    INSTANCE.get_or_init(|| Mutex::new(load_preferences()))
+}
+
 /// Persistent user preferences
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -58,6 +76,7 @@ struct UserPreferences {
     line_width: u32,
     view_scale: u32,
     brightness: u32,
+    vibrancy: u32,
     fps: u32,
     #[serde(default)]
     run_on_login: bool,
@@ -65,6 +84,16 @@ struct UserPreferences {
     custom_color_wheel: Option<[f32; 24]>,
     #[serde(default)]
     custom_image_path: Option<String>,
+    #[serde(default)]
+    slideshow_path: Option<String>,
+    slideshow_interval: u32,
+    slideshow_crossfade: u32,
+    #[serde(default)]
+    animated_image_path: Option<String>,
+    #[serde(default)]
+    hdr: bool,
+    #[serde(default)]
+    display_p3: bool,
 }
 
 impl Default for UserPreferences {
@@ -77,10 +106,17 @@ impl Default for UserPreferences {
             line_width: 1,     // Medium
             view_scale: 1,     // Normal
             brightness: 1,     // Normal
+            vibrancy: 0,       // Muted
             fps: 30,
             run_on_login: false,
             custom_color_wheel: None,
             custom_image_path: None,
+            slideshow_path: None,
+            slideshow_interval: 300, // 5 minutes
+            slideshow_crossfade: 5,
+            animated_image_path: None,
+            hdr: false,
+            display_p3: false,
         }
     }
 }
@@ -120,6 +156,9 @@ fn save_preferences(prefs: &UserPreferences) {
     if let Ok(json) = serde_json::to_string_pretty(prefs) {
         let _ = std::fs::write(path, json);
     }
+    if let Ok(mut guard) = cached_preferences().lock() {
+        *guard = prefs.clone();
+    }
 }
 
 /// Convert density setting to grid_spacing value
@@ -133,9 +172,18 @@ fn density_to_grid_spacing(density: u32) -> u32 {
     }
 }
 
-/// Get color preset from scheme index
-fn scheme_to_color_mode(scheme: u32) -> flux::settings::ColorMode {
-    use flux::settings::{ColorMode, ColorPreset};
+/// Get color mode from scheme index
+fn scheme_to_color_mode(scheme: u32, prefs: &UserPreferences) -> flux::settings::ColorMode {
+    use flux::settings::{ColorMode, ColorPreset, Slideshow};
+    match (scheme, &prefs.slideshow_path, &prefs.animated_image_path) {
+        (5, Some(path), _) => return ColorMode::Slideshow(Slideshow {
+            directory: path.into(),
+            interval: prefs.slideshow_interval as f32,
+            crossfade: prefs.slideshow_crossfade as f32,
+        }),
+        (6, _, Some(path)) => return ColorMode::AnimatedImageFile(path.into()),
+        _ => (),
+    }
     match scheme {
         0 => ColorMode::Preset(ColorPreset::Original),
         1 => ColorMode::Preset(ColorPreset::Plasma),
@@ -147,6 +195,66 @@ fn scheme_to_color_mode(scheme: u32) -> flux::settings::ColorMode {
     }
 }
 
+/// Ask for a folder of images and switch the color scheme to a slideshow of them
+/// Blocks while the dialog is open and the images decode, so call from a background thread
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn choose_slideshow_directory() -> bool {
+    let dialog = rfd::FileDialog::new().set_title("Choose a folder of images for the slideshow");
+    let Some(path) = dialog.pick_folder() else {
+        log::info!("Slideshow folder dialog cancelled");
+        return false;
+    };
+
+    let mut prefs = load_preferences();
+    prefs.slideshow_path = Some(path.to_string_lossy().to_string());
+    load_color_animation(5, prefs)
+}
+
+/// Ask for an animated GIF or APNG and switch the color scheme to it
+/// Blocks while the dialog is open and the image decodes, so call from a background thread
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn choose_animated_image() -> bool {
+    let dialog = rfd::FileDialog::new()
+        .add_filter("Animated Images", &["gif", "png", "apng"])
+        .set_title("Choose an animated image for colors");
+    let Some(path) = dialog.pick_file() else {
+        log::info!("Animated image file dialog cancelled");
+        return false;
+    };
+
+    let mut prefs = load_preferences();
+    prefs.animated_image_path = Some(path.to_string_lossy().to_string());
+    load_color_animation(6, prefs)
+}
+
+/// Load the slideshow or animated image for `scheme`, and on success make it the pending
+/// color animation and save the scheme to preferences
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn load_color_animation(scheme: u32, mut prefs: UserPreferences) -> bool {
+    let color_mode = scheme_to_color_mode(scheme, &prefs);
+    match flux::render::color::Animation::from_color_mode(&color_mode) {
+        Ok(Some(animation)) => {
+            log::info!("Loaded color animation with {} frames", animation.frame_count());
+            if let Ok(mut guard) = pending_color_animation().lock() {
+                *guard = Some(animation);
+            }
+            prefs.color_scheme = scheme;
+            save_preferences(&prefs);
+            CURRENT_COLOR_SCHEME.store(scheme, Ordering::SeqCst);
+            SETTINGS_CHANGED.store(true, Ordering::SeqCst);
+            true
+        }
+        Ok(None) => {
+            log::error!("No slideshow folder or animated image set for color scheme {}", scheme);
+            false
+        }
+        Err(err) => {
+            log::error!("Failed to load color animation: {}", err);
+            false
+        }
+    }
+}
+
 /// Convert HSL values to RGB floats (0.0-1.0)
 fn hsl_to_rgb_f32(h: f32, s: f32, l: f32) -> (f32, f32, f32) {
     if s == 0.0 {
@@ -329,6 +437,26 @@ fn brightness_to_multiplier(brightness: u32) -> f32 {
     }
 }
 
+/// Convert vibrancy setting to (max_saturation, max_lightness, base_intensity)
+fn vibrancy_to_caps(vibrancy: u32) -> (f32, f32, f32) {
+    match vibrancy {
+        0 => (0.25, 0.30, 0.3), // Muted (default)
+        1 => (0.45, 0.40, 0.4), // Balanced
+        2 => (0.70, 0.50, 0.5), // Rich
+        3 => (1.00, 0.60, 0.6), // Full
+        _ => (0.25, 0.30, 0.3),
+    }
+}
+
+/// Convert the Display P3 setting to an output color space
+fn display_p3_to_color_space(display_p3: bool) -> flux::settings::ColorSpace {
+    if display_p3 {
+        flux::settings::ColorSpace::DisplayP3
+    } else {
+        flux::settings::ColorSpace::Srgb
+    }
+}
+
 #[derive(Parser, Debug, Clone)]
 #[command(name = "drift", about = "Drift - A live wallpaper inspired by macOS Drift")]
 struct Args {
@@ -351,10 +479,12 @@ struct App {
     settings: Arc<Settings>,
 
     color_image: Arc<Mutex<Option<RgbaImage>>>,
+    color_animation: Arc<Mutex<Option<flux::render::color::Animation>>>,
 }
 
 enum Msg {
     DecodedImage,
+    DecodedAnimation,
 }
 
 impl App {
@@ -366,11 +496,21 @@ impl App {
                         self.flux.sample_colors_from_image(device, queue, image);
                     }
                 }
+                Msg::DecodedAnimation => {
+                    if let Some(animation) = self.color_animation.lock().unwrap().take() {
+                        self.flux.sample_colors_from_animation(device, queue, animation);
+                    }
+                }
             }
         }
     }
 
     pub fn decode_image(&self, encoded_bytes: Vec<u8>) {
+        if flux::render::color::is_animated(&encoded_bytes) {
+            self.load_animation(move || flux::render::color::decode_animation(&encoded_bytes));
+            return;
+        }
+
         let tx = self.tx.clone();
         let color_image = Arc::clone(&self.color_image);
         self.runtime.spawn(async move {
@@ -389,6 +529,40 @@ impl App {
         });
         log::debug!("Spawned image decoding task");
     }
+
+    pub fn load_slideshow(&self, directory: std::path::PathBuf) {
+        let prefs = load_preferences();
+        let interval = prefs.slideshow_interval as f32;
+        let crossfade = prefs.slideshow_crossfade as f32;
+        self.load_animation(move || {
+            flux::render::color::load_slideshow(&directory, interval, crossfade)
+        });
+    }
+
+    fn load_animation<F>(&self, load: F)
+    where
+        F: FnOnce() -> Result<flux::render::color::Animation, flux::render::color::Problem>
+            + Send
+            + 'static,
+    {
+        let tx = self.tx.clone();
+        let color_animation = Arc::clone(&self.color_animation);
+        self.runtime.spawn(async move {
+            match load() {
+                Ok(animation) => {
+                    {
+                        let mut pending = color_animation.lock().unwrap();
+                        *pending = Some(animation);
+                    }
+                    if tx.send(Msg::DecodedAnimation).await.is_err() {
+                        log::error!("Failed to send decoded animation message");
+                    }
+                }
+                Err(err) => log::error!("{}", err),
+            }
+        });
+        log::debug!("Spawned animation loading task");
+    }
 }
 
 /// Display info for wallpaper mode
@@ -401,6 +575,8 @@ struct DisplayInfo {
     // Physical pixel dimensions (for wgpu surface)
     pixels_wide: u32,
     pixels_high: u32,
+    // Areas covered by the menu bar, Dock or taskbar
+    safe_zones: SafeZones,
 }
 
 #[cfg(target_os = "macos")]
@@ -649,6 +825,14 @@ fn get_all_displays() -> Vec<DisplayInfo> {
                 pixels_wide, pixels_high
             );
 
+            // The visible frame excludes the menu bar and Dock. AppKit's y axis points up.
+            let safe_zones = SafeZones {
+                top: ((frame.origin.y + frame.size.height) - (visible_frame.origin.y + visible_frame.size.height)) as f32 / frame.size.height as f32,
+                bottom: (visible_frame.origin.y - frame.origin.y) as f32 / frame.size.height as f32,
+                left: (visible_frame.origin.x - frame.origin.x) as f32 / frame.size.width as f32,
+                right: ((frame.origin.x + frame.size.width) - (visible_frame.origin.x + visible_frame.size.width)) as f32 / frame.size.width as f32,
+            };
+
             displays.push(DisplayInfo {
                 origin_x: frame.origin.x,
                 origin_y: frame.origin.y,
@@ -656,6 +840,7 @@ fn get_all_displays() -> Vec<DisplayInfo> {
                 height: frame.size.height,
                 pixels_wide,
                 pixels_high,
+                safe_zones,
             });
         }
     }
@@ -672,6 +857,7 @@ fn get_all_displays() -> Vec<DisplayInfo> {
             height: bounds.size.height,
             pixels_wide: display.pixels_wide() as u32,
             pixels_high: display.pixels_high() as u32,
+            safe_zones: SafeZones::default(),
         });
     }
 
@@ -860,6 +1046,15 @@ fn get_all_displays() -> Vec<DisplayInfo> {
             let width = (rect.right - rect.left) as f64;
             let height = (rect.bottom - rect.top) as f64;
 
+            // The work area excludes the taskbar
+            let work = info.rcWork;
+            let safe_zones = SafeZones {
+                top: (work.top - rect.top) as f32 / height as f32,
+                bottom: (rect.bottom - work.bottom) as f32 / height as f32,
+                left: (work.left - rect.left) as f32 / width as f32,
+                right: (rect.right - work.right) as f32 / width as f32,
+            };
+
             DISPLAYS.push(DisplayInfo {
                 origin_x: rect.left as f64,
                 origin_y: rect.top as f64,
@@ -867,6 +1062,7 @@ fn get_all_displays() -> Vec<DisplayInfo> {
                 height,
                 pixels_wide: width as u32,
                 pixels_high: height as u32,
+                safe_zones,
             });
         }
         TRUE
@@ -885,6 +1081,7 @@ fn get_all_displays() -> Vec<DisplayInfo> {
                 height: 1080.0,
                 pixels_wide: 1920,
                 pixels_high: 1080,
+                safe_zones: SafeZones::default(),
             }]
         } else {
             DISPLAYS.clone()
@@ -906,6 +1103,7 @@ fn get_all_displays() -> Vec<DisplayInfo> {
         height: 1080.0,
         pixels_wide: 1920,
         pixels_high: 1080,
+        safe_zones: SafeZones::default(),
     }]
 }
 
@@ -971,7 +1169,8 @@ fn disable_launch_at_login() {
 }
 
 /// Setup macOS screen configuration change observer
-/// This monitors for display resolution changes, display add/remove, etc.
+/// This monitors for display resolution changes, display add/remove, and moves of
+/// the menu bar or Dock, which change the visible frame.
 #[cfg(target_os = "macos")]
 fn setup_screen_change_observer() {
     use cocoa::base::{id, nil};
@@ -1021,9 +1220,67 @@ fn setup_screen_change_observer() {
     }
 }
 
-#[cfg(not(target_os = "macos"))]
+/// Setup Windows screen configuration change observer
+/// Display changes and work area changes (a moved or resized taskbar) are only
+/// broadcast to top-level windows, so listen with a hidden one on its own thread.
+#[cfg(target_os = "windows")]
 fn setup_screen_change_observer() {
-    log::warn!("Screen change observer is only supported on macOS");
+    use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+    use windows_sys::Win32::System::LibraryLoader::GetModuleHandleA;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        CreateWindowExA, DefWindowProcA, DispatchMessageA, GetMessageA, RegisterClassA,
+        TranslateMessage, MSG, SPI_SETWORKAREA, WM_DISPLAYCHANGE, WM_SETTINGCHANGE, WNDCLASSA,
+        WS_OVERLAPPED,
+    };
+
+    unsafe extern "system" fn window_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        let work_area_changed = msg == WM_SETTINGCHANGE && wparam == SPI_SETWORKAREA as WPARAM;
+        if msg == WM_DISPLAYCHANGE || work_area_changed {
+            log::info!("Screen configuration changed - will reinitialize displays");
+            SCREEN_CONFIG_CHANGED.store(true, Ordering::SeqCst);
+        }
+        DefWindowProcA(hwnd, msg, wparam, lparam)
+    }
+
+    std::thread::spawn(|| unsafe {
+        let instance = GetModuleHandleA(std::ptr::null());
+        let class_name = b"DriftScreenChangeObserver\0".as_ptr();
+
+        let mut window_class: WNDCLASSA = std::mem::zeroed();
+        window_class.lpfnWndProc = Some(window_proc);
+        window_class.hInstance = instance;
+        window_class.lpszClassName = class_name;
+        RegisterClassA(&window_class);
+
+        // Never shown. Message-only windows don't receive broadcasts.
+        let hwnd = CreateWindowExA(
+            0,
+            class_name,
+            class_name,
+            WS_OVERLAPPED,
+            0, 0, 0, 0,
+            0 as HWND,
+            0,
+            instance,
+            std::ptr::null(),
+        );
+        if hwnd == 0 as HWND {
+            log::error!("Failed to create screen change observer window");
+            return;
+        }
+        log::info!("Screen change observer registered");
+
+        let mut msg: MSG = std::mem::zeroed();
+        while GetMessageA(&mut msg, 0 as HWND, 0, 0) > 0 {
+            TranslateMessage(&msg);
+            DispatchMessageA(&msg);
+        }
+    });
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn setup_screen_change_observer() {
+    log::warn!("Screen change observer is only supported on macOS and Windows");
 }
 
 /// Setup macOS menu bar item for wallpaper control
@@ -1059,6 +1316,34 @@ fn setup_menu_bar() {
         }
     }
 
+    extern "C" fn toggle_hdr_action(_this: &Object, _cmd: Sel, sender: id) {
+        let enabled = !HDR_ENABLED.load(Ordering::SeqCst);
+        log::info!("HDR changed to: {}", enabled);
+        HDR_ENABLED.store(enabled, Ordering::SeqCst);
+        SETTINGS_CHANGED.store(true, Ordering::SeqCst);
+        let mut prefs = load_preferences();
+        prefs.hdr = enabled;
+        save_preferences(&prefs);
+        unsafe {
+            let new_state: i64 = if enabled { 1 } else { 0 };
+            let _: () = msg_send![sender, setState: new_state];
+        }
+    }
+
+    extern "C" fn toggle_display_p3_action(_this: &Object, _cmd: Sel, sender: id) {
+        let enabled = !DISPLAY_P3_ENABLED.load(Ordering::SeqCst);
+        log::info!("Display P3 changed to: {}", enabled);
+        DISPLAY_P3_ENABLED.store(enabled, Ordering::SeqCst);
+        SETTINGS_CHANGED.store(true, Ordering::SeqCst);
+        let mut prefs = load_preferences();
+        prefs.display_p3 = enabled;
+        save_preferences(&prefs);
+        unsafe {
+            let new_state: i64 = if enabled { 1 } else { 0 };
+            let _: () = msg_send![sender, setState: new_state];
+        }
+    }
+
     extern "C" fn set_color_original(_this: &Object, _cmd: Sel, sender: id) {
         log::info!("set_color_original action triggered");
         set_color_scheme(0, sender);
@@ -1126,6 +1411,38 @@ fn setup_menu_bar() {
         }
     }
 
+    extern "C" fn set_color_slideshow(_this: &Object, _cmd: Sel, sender: id) {
+        log::info!("set_color_slideshow action triggered");
+        // Open folder dialog and decode images on a separate thread to avoid blocking the menu
+        std::thread::spawn(move || {
+            choose_slideshow_directory();
+        });
+        update_color_checkmarks(5, sender);
+    }
+
+    extern "C" fn set_color_animated_image(_this: &Object, _cmd: Sel, sender: id) {
+        log::info!("set_color_animated_image action triggered");
+        std::thread::spawn(move || {
+            choose_animated_image();
+        });
+        update_color_checkmarks(6, sender);
+    }
+
+    fn update_color_checkmarks(scheme: u32, sender: id) {
+        unsafe {
+            let menu: id = msg_send![sender, menu];
+            if menu != nil {
+                let count: i64 = msg_send![menu, numberOfItems];
+                for i in 0..count {
+                    let item: id = msg_send![menu, itemAtIndex: i];
+                    let tag: i64 = msg_send![item, tag];
+                    let state: i64 = if tag == scheme as i64 { 1 } else { 0 };
+                    let _: () = msg_send![item, setState: state];
+                }
+            }
+        }
+    }
+
     fn set_color_scheme(scheme: u32, sender: id) {
         log::info!("Setting color scheme to: {}", scheme);
         CURRENT_COLOR_SCHEME.store(scheme, Ordering::SeqCst);
@@ -1374,6 +1691,46 @@ fn setup_menu_bar() {
         }
     }
 
+    // ===== Vibrancy Handlers =====
+    extern "C" fn set_vibrancy_muted(_this: &Object, _cmd: Sel, sender: id) {
+        log::info!("set_vibrancy_muted action triggered");
+        set_vibrancy(0, sender);
+    }
+
+    extern "C" fn set_vibrancy_balanced(_this: &Object, _cmd: Sel, sender: id) {
+        log::info!("set_vibrancy_balanced action triggered");
+        set_vibrancy(1, sender);
+    }
+
+    extern "C" fn set_vibrancy_rich(_this: &Object, _cmd: Sel, sender: id) {
+        log::info!("set_vibrancy_rich action triggered");
+        set_vibrancy(2, sender);
+    }
+
+    extern "C" fn set_vibrancy_full(_this: &Object, _cmd: Sel, sender: id) {
+        log::info!("set_vibrancy_full action triggered");
+        set_vibrancy(3, sender);
+    }
+
+    fn set_vibrancy(vibrancy: u32, sender: id) {
+        log::info!("Vibrancy changed to: {}", vibrancy);
+        CURRENT_VIBRANCY.store(vibrancy, Ordering::SeqCst);
+        SETTINGS_CHANGED.store(true, Ordering::SeqCst);
+        let mut prefs = load_preferences();
+        prefs.vibrancy = vibrancy;
+        save_preferences(&prefs);
+        unsafe {
+            let menu: id = msg_send![sender, menu];
+            let count: i64 = msg_send![menu, numberOfItems];
+            for i in 0..count {
+                let item: id = msg_send![menu, itemAtIndex: i];
+                let tag: i64 = msg_send![item, tag];
+                let state: i64 = if tag == vibrancy as i64 { 1 } else { 0 };
+                let _: () = msg_send![item, setState: state];
+            }
+        }
+    }
+
     // Delegate method to update menu when opened
     extern "C" fn menu_will_open(_this: &Object, _cmd: Sel, menu: id) {
         // Update login item state when menu opens
@@ -1408,6 +1765,9 @@ fn setup_menu_bar() {
         CURRENT_LINE_WIDTH.store(prefs.line_width, Ordering::SeqCst);
         CURRENT_VIEW_SCALE.store(prefs.view_scale, Ordering::SeqCst);
         CURRENT_BRIGHTNESS.store(prefs.brightness, Ordering::SeqCst);
+        CURRENT_VIBRANCY.store(prefs.vibrancy, Ordering::SeqCst);
+        HDR_ENABLED.store(prefs.hdr, Ordering::SeqCst);
+        DISPLAY_P3_ENABLED.store(prefs.display_p3, Ordering::SeqCst);
 
         // Load cached custom color wheel if available
         if prefs.color_scheme == 4 {
@@ -1435,11 +1795,15 @@ fn setup_menu_bar() {
             let mut decl = ClassDecl::new(class_name, superclass).unwrap();
             decl.add_method(sel!(quitAction:), quit_action as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(toggleLoginAction:), toggle_login_action as extern "C" fn(&Object, Sel, id));
+            decl.add_method(sel!(toggleHdrAction:), toggle_hdr_action as extern "C" fn(&Object, Sel, id));
+            decl.add_method(sel!(toggleDisplayP3Action:), toggle_display_p3_action as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(setColorOriginal:), set_color_original as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(setColorPlasma:), set_color_plasma as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(setColorPoolside:), set_color_poolside as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(setColorSpacegrey:), set_color_spacegrey as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(setColorCustomImage:), set_color_custom_image as extern "C" fn(&Object, Sel, id));
+            decl.add_method(sel!(setColorSlideshow:), set_color_slideshow as extern "C" fn(&Object, Sel, id));
+            decl.add_method(sel!(setColorAnimatedImage:), set_color_animated_image as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(setDensitySparse:), set_density_sparse as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(setDensityNormal:), set_density_normal as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(setDensityDense:), set_density_dense as extern "C" fn(&Object, Sel, id));
@@ -1461,6 +1825,10 @@ fn setup_menu_bar() {
             decl.add_method(sel!(setBrightnessNormal:), set_brightness_normal as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(setBrightnessBright:), set_brightness_bright as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(setBrightnessVivid:), set_brightness_vivid as extern "C" fn(&Object, Sel, id));
+            decl.add_method(sel!(setVibrancyMuted:), set_vibrancy_muted as extern "C" fn(&Object, Sel, id));
+            decl.add_method(sel!(setVibrancyBalanced:), set_vibrancy_balanced as extern "C" fn(&Object, Sel, id));
+            decl.add_method(sel!(setVibrancyRich:), set_vibrancy_rich as extern "C" fn(&Object, Sel, id));
+            decl.add_method(sel!(setVibrancyFull:), set_vibrancy_full as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(menuWillOpen:), menu_will_open as extern "C" fn(&Object, Sel, id));
             let handler_class = decl.register();
             handler = msg_send![handler_class, new];
@@ -1543,6 +1911,24 @@ fn setup_menu_bar() {
         }
         color_menu.addItem_(custom_item);
 
+        // "Image Slideshow..." and "Animated Image..." menu items
+        let animation_items = [
+            ("Image Slideshow...", sel!(setColorSlideshow:), 5i64),
+            ("Animated Image...", sel!(setColorAnimatedImage:), 6i64),
+        ];
+        for (name, action, tag) in animation_items.iter() {
+            let item_title = NSString::alloc(nil).init_str(name);
+            let item: id = msg_send![class!(NSMenuItem), alloc];
+            let item: id = msg_send![item, initWithTitle:item_title action:*action keyEquivalent:NSString::alloc(nil).init_str("")];
+            let _: () = msg_send![item, setTarget: handler];
+            let _: () = msg_send![item, setTag: *tag];
+            let _: () = msg_send![item, setEnabled: YES];
+            if prefs.color_scheme as i64 == *tag {
+                let _: () = msg_send![item, setState: 1i64]; // NSOnState
+            }
+            color_menu.addItem_(item);
+        }
+
         let _: () = msg_send![color_item, setSubmenu: color_menu];
         menu.addItem_(color_item);
 
@@ -1750,6 +2136,67 @@ fn setup_menu_bar() {
         let _: () = msg_send![brightness_item, setSubmenu: brightness_menu];
         menu.addItem_(brightness_item);
 
+        // ===== Vibrancy Submenu =====
+        let vibrancy_title = NSString::alloc(nil).init_str("Vibrancy");
+        let vibrancy_item = NSMenuItem::alloc(nil).initWithTitle_action_keyEquivalent_(
+            vibrancy_title,
+            selector(""),
+            NSString::alloc(nil).init_str(""),
+        );
+
+        let vibrancy_menu = NSMenu::new(nil).autorelease();
+        let _: () = msg_send![vibrancy_menu, setAutoenablesItems: NO];
+
+        let vibrancy_names = ["Muted", "Balanced", "Rich", "Full"];
+        let vibrancy_selectors = [
+            sel!(setVibrancyMuted:),
+            sel!(setVibrancyBalanced:),
+            sel!(setVibrancyRich:),
+            sel!(setVibrancyFull:),
+        ];
+
+        for (i, (name, action)) in vibrancy_names.iter().zip(vibrancy_selectors.iter()).enumerate() {
+            let item_title = NSString::alloc(nil).init_str(name);
+            let item: id = msg_send![class!(NSMenuItem), alloc];
+            let item: id = msg_send![item, initWithTitle:item_title action:*action keyEquivalent:NSString::alloc(nil).init_str("")];
+            let _: () = msg_send![item, setTarget: handler];
+            let _: () = msg_send![item, setTag: i as i64];
+            let _: () = msg_send![item, setEnabled: YES];
+            if i as u32 == prefs.vibrancy {
+                let _: () = msg_send![item, setState: 1i64];
+            }
+            vibrancy_menu.addItem_(item);
+        }
+
+        let _: () = msg_send![vibrancy_item, setSubmenu: vibrancy_menu];
+        menu.addItem_(vibrancy_item);
+
+        // ===== HDR =====
+        let hdr_title = NSString::alloc(nil).init_str("HDR");
+        let hdr_item = NSMenuItem::alloc(nil).initWithTitle_action_keyEquivalent_(
+            hdr_title,
+            selector("toggleHdrAction:"),
+            NSString::alloc(nil).init_str(""),
+        );
+        let _: () = msg_send![hdr_item, setTarget: handler];
+        if prefs.hdr {
+            let _: () = msg_send![hdr_item, setState: 1i64];
+        }
+        menu.addItem_(hdr_item);
+
+        // ===== Display P3 =====
+        let p3_title = NSString::alloc(nil).init_str("Display P3");
+        let p3_item = NSMenuItem::alloc(nil).initWithTitle_action_keyEquivalent_(
+            p3_title,
+            selector("toggleDisplayP3Action:"),
+            NSString::alloc(nil).init_str(""),
+        );
+        let _: () = msg_send![p3_item, setTarget: handler];
+        if prefs.display_p3 {
+            let _: () = msg_send![p3_item, setState: 1i64];
+        }
+        menu.addItem_(p3_item);
+
         // ===== Separator =====
         let separator1: id = msg_send![class!(NSMenuItem), separatorItem];
         menu.addItem_(separator1);
@@ -1805,6 +2252,7 @@ fn setup_menu_bar() {
         let _: () = msg_send![line_width_menu, retain];
         let _: () = msg_send![view_scale_menu, retain];
         let _: () = msg_send![brightness_menu, retain];
+        let _: () = msg_send![vibrancy_menu, retain];
 
         // Store in static to prevent deallocation
         static mut STATUS_ITEM: *mut Object = std::ptr::null_mut();
@@ -1899,6 +2347,9 @@ fn setup_menu_bar() -> Option<tray_icon::TrayIcon> {
     CURRENT_LINE_WIDTH.store(prefs.line_width, Ordering::SeqCst);
     CURRENT_VIEW_SCALE.store(prefs.view_scale, Ordering::SeqCst);
     CURRENT_BRIGHTNESS.store(prefs.brightness, Ordering::SeqCst);
+    CURRENT_VIBRANCY.store(prefs.vibrancy, Ordering::SeqCst);
+    HDR_ENABLED.store(prefs.hdr, Ordering::SeqCst);
+    DISPLAY_P3_ENABLED.store(prefs.display_p3, Ordering::SeqCst);
 
     // Create menu
     let menu = Menu::new();
@@ -1910,12 +2361,16 @@ fn setup_menu_bar() -> Option<tray_icon::TrayIcon> {
     let color_poolside = CheckMenuItem::new("Poolside", true, prefs.color_scheme == 2, None);
     let color_spacegrey = CheckMenuItem::new("Space Grey", true, prefs.color_scheme == 3, None);
     let color_custom = CheckMenuItem::new("Custom Image...", true, prefs.color_scheme == 4, None);
+    let color_slideshow = CheckMenuItem::new("Image Slideshow...", true, prefs.color_scheme == 5, None);
+    let color_animated = CheckMenuItem::new("Animated Image...", true, prefs.color_scheme == 6, None);
     let _ = color_submenu.append(&color_original);
     let _ = color_submenu.append(&color_plasma);
     let _ = color_submenu.append(&color_poolside);
     let _ = color_submenu.append(&color_spacegrey);
     let _ = color_submenu.append(&muda::PredefinedMenuItem::separator());
     let _ = color_submenu.append(&color_custom);
+    let _ = color_submenu.append(&color_slideshow);
+    let _ = color_submenu.append(&color_animated);
     let _ = menu.append(&color_submenu);
 
     // Density submenu
@@ -1984,6 +2439,22 @@ fn setup_menu_bar() -> Option<tray_icon::TrayIcon> {
     let _ = brightness_submenu.append(&brightness_vivid);
     let _ = menu.append(&brightness_submenu);
 
+    // Vibrancy submenu
+    let vibrancy_submenu = Submenu::new("Vibrancy", true);
+    let vibrancy_muted = CheckMenuItem::new("Muted", true, prefs.vibrancy == 0, None);
+    let vibrancy_balanced = CheckMenuItem::new("Balanced", true, prefs.vibrancy == 1, None);
+    let vibrancy_rich = CheckMenuItem::new("Rich", true, prefs.vibrancy == 2, None);
+    let vibrancy_full = CheckMenuItem::new("Full", true, prefs.vibrancy == 3, None);
+    let _ = vibrancy_submenu.append(&vibrancy_muted);
+    let _ = vibrancy_submenu.append(&vibrancy_balanced);
+    let _ = vibrancy_submenu.append(&vibrancy_rich);
+    let _ = vibrancy_submenu.append(&vibrancy_full);
+    let _ = menu.append(&vibrancy_submenu);
+
+    // HDR item
+    let hdr_item = CheckMenuItem::new("HDR", true, prefs.hdr, None);
+    let _ = menu.append(&hdr_item);
+
     let _ = menu.append(&PredefinedMenuItem::separator());
 
     // Run on Login item
@@ -2029,7 +2500,7 @@ fn setup_menu_bar() -> Option<tray_icon::TrayIcon> {
     log::info!("Windows system tray created");
 
     // Extract string IDs before spawning thread (MenuId contains Rc which is not Send)
-    let color_ids: Vec<String> = [&color_original, &color_plasma, &color_poolside, &color_spacegrey, &color_custom]
+    let color_ids: Vec<String> = [&color_original, &color_plasma, &color_poolside, &color_spacegrey, &color_custom, &color_slideshow, &color_animated]
         .iter().map(|item| item.id().0.clone()).collect();
     let density_ids: Vec<String> = [&density_sparse, &density_normal, &density_dense]
         .iter().map(|item| item.id().0.clone()).collect();
@@ -2043,6 +2514,9 @@ fn setup_menu_bar() -> Option<tray_icon::TrayIcon> {
         .iter().map(|item| item.id().0.clone()).collect();
     let brightness_ids: Vec<String> = [&brightness_dim, &brightness_normal, &brightness_bright, &brightness_vivid]
         .iter().map(|item| item.id().0.clone()).collect();
+    let vibrancy_ids: Vec<String> = [&vibrancy_muted, &vibrancy_balanced, &vibrancy_rich, &vibrancy_full]
+        .iter().map(|item| item.id().0.clone()).collect();
+    let hdr_id_str = hdr_item.id().0.clone();
     let run_on_login_id_str = run_on_login_item.id().0.clone();
     let quit_id_str = quit_id.0.clone();
 
@@ -2087,6 +2561,14 @@ fn setup_menu_bar() -> Option<tray_icon::TrayIcon> {
                                 log::info!("Custom image file dialog cancelled");
                                 continue;
                             }
+                        } else if i == 5 {
+                            if !choose_slideshow_directory() {
+                                continue;
+                            }
+                        } else if i == 6 {
+                            if !choose_animated_image() {
+                                continue;
+                            }
                         } else {
                             CURRENT_COLOR_SCHEME.store(i as u32, Ordering::SeqCst);
                             SETTINGS_CHANGED.store(true, Ordering::SeqCst);
@@ -2170,6 +2652,29 @@ fn setup_menu_bar() -> Option<tray_icon::TrayIcon> {
                     }
                 }
 
+                // Check vibrancy
+                for (i, vibrancy_id) in vibrancy_ids.iter().enumerate() {
+                    if id_str == vibrancy_id {
+                        CURRENT_VIBRANCY.store(i as u32, Ordering::SeqCst);
+                        SETTINGS_CHANGED.store(true, Ordering::SeqCst);
+                        let mut prefs = load_preferences();
+                        prefs.vibrancy = i as u32;
+                        save_preferences(&prefs);
+                        log::info!("Vibrancy changed to {}", i);
+                    }
+                }
+
+                // Check HDR toggle
+                if id_str == &hdr_id_str {
+                    let enabled = !HDR_ENABLED.load(Ordering::SeqCst);
+                    HDR_ENABLED.store(enabled, Ordering::SeqCst);
+                    SETTINGS_CHANGED.store(true, Ordering::SeqCst);
+                    let mut prefs = load_preferences();
+                    prefs.hdr = enabled;
+                    save_preferences(&prefs);
+                    log::info!("HDR changed to {}", enabled);
+                }
+
                 // Check run on login toggle
                 if id_str == &run_on_login_id_str {
                     // Toggle the current state
@@ -2302,6 +2807,7 @@ struct DisplayRenderer {
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
+    capabilities: wgpu::SurfaceCapabilities,
     flux: Flux,
     display_info: DisplayInfo,
 }
@@ -2318,24 +2824,39 @@ async fn run_wallpaper_multi(
     // Load user preferences and apply to settings
     let prefs = load_preferences();
     let mut settings = Settings::default();
-    settings.color_mode = scheme_to_color_mode(prefs.color_scheme);
+    settings.color_mode = scheme_to_color_mode(prefs.color_scheme, &prefs);
     settings.grid_spacing = density_to_grid_spacing(prefs.density);
     settings.noise_multiplier = noise_strength_to_multiplier(prefs.noise_strength);
     settings.line_length = line_length_to_value(prefs.line_length);
     settings.line_width = line_width_to_value(prefs.line_width);
     settings.view_scale = view_scale_to_value(prefs.view_scale);
     settings.brightness_multiplier = brightness_to_multiplier(prefs.brightness);
+    (settings.max_saturation, settings.max_lightness, settings.base_intensity) = vibrancy_to_caps(prefs.vibrancy);
+    settings.hdr = prefs.hdr;
+    settings.color_space = display_p3_to_color_space(prefs.display_p3);
+
+    // Load the slideshow or animated image once and share it between displays
+    let startup_color_animation =
+        match flux::render::color::Animation::from_color_mode(&settings.color_mode) {
+            Ok(animation) => animation,
+            Err(err) => {
+                log::error!("Failed to load color animation, falling back to Original: {}", err);
+                settings.color_mode = scheme_to_color_mode(0, &prefs);
+                None
+            }
+        };
     let settings = Arc::new(settings);
 
     log::info!(
-        "Applied settings from preferences: color={}, density={}, noise={}, line_length={}, line_width={}, view_scale={}, brightness={}",
+        "Applied settings from preferences: color={}, density={}, noise={}, line_length={}, line_width={}, view_scale={}, brightness={}, vibrancy={}",
         prefs.color_scheme,
         prefs.density,
         prefs.noise_strength,
         prefs.line_length,
         prefs.line_width,
         prefs.view_scale,
-        prefs.brightness
+        prefs.brightness,
+        prefs.vibrancy
     );
 
     // Initialize each display
@@ -2389,7 +2910,7 @@ async fn run_wallpaper_multi(
             .expect("Failed to create device");
 
         let swapchain_capabilities = surface.get_capabilities(&adapter);
-        let swapchain_format = get_preferred_format(&swapchain_capabilities);
+        let swapchain_format = get_preferred_format(&swapchain_capabilities, settings.hdr);
         log::info!(
             "Surface format: {:?}, alpha modes: {:?}",
             swapchain_format,
@@ -2416,7 +2937,9 @@ async fn run_wallpaper_multi(
         );
 
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // Frames are copied to crossfade from when the lines are laid out again
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (swapchain_capabilities.usages & wgpu::TextureUsages::COPY_SRC),
             format: swapchain_format,
             width: physical_width.max(1),
             height: physical_height.max(1),
@@ -2427,6 +2950,7 @@ async fn run_wallpaper_multi(
         };
 
         surface.configure(&device, &config);
+        set_surface_color_space(&window, swapchain_format, settings.color_space);
 
         let mut flux = Flux::new(
             &device,
@@ -2439,6 +2963,7 @@ async fn run_wallpaper_multi(
             &Arc::clone(&settings),
         )
         .unwrap();
+        flux.set_safe_zones(display.safe_zones);
 
         // Inject cached custom color wheel on startup if scheme is Custom Image
         if prefs.color_scheme == 4 {
@@ -2451,12 +2976,18 @@ async fn run_wallpaper_multi(
                         mapped_at_creation: false,
                     });
                     queue.write_buffer(&color_buffer, 0, bytemuck::cast_slice(&wheel));
-                    flux.lines.update_color_bindings(&device, &queue, None, Some(color_buffer));
+                    flux.sample_colors_from_buffer(&device, &queue, color_buffer);
                     log::info!("Injected cached custom color wheel on startup");
                 }
             }
         }
 
+        // Inject slideshow or animated image on startup
+        if let Some(animation) = &startup_color_animation {
+            flux.sample_colors_from_animation(&device, &queue, animation.clone());
+            log::info!("Injected color animation on startup");
+        }
+
         window.set_visible(true);
 
         // Re-apply setIgnoresMouseEvents after window is visible
@@ -2496,6 +3027,7 @@ async fn run_wallpaper_multi(
             device,
             queue,
             config,
+            capabilities: swapchain_capabilities,
             flux,
             display_info: display,
         });
@@ -2525,17 +3057,22 @@ async fn run_wallpaper_multi(
             let new_line_width = CURRENT_LINE_WIDTH.load(Ordering::SeqCst);
             let new_view_scale = CURRENT_VIEW_SCALE.load(Ordering::SeqCst);
             let new_brightness = CURRENT_BRIGHTNESS.load(Ordering::SeqCst);
-            log::info!("Applying live settings update: color={}, density={}, noise={}, line_length={}, line_width={}, view_scale={}, brightness={}",
-                new_color, new_density, new_noise, new_line_length, new_line_width, new_view_scale, new_brightness);
+            let new_vibrancy = CURRENT_VIBRANCY.load(Ordering::SeqCst);
+            log::info!("Applying live settings update: color={}, density={}, noise={}, line_length={}, line_width={}, view_scale={}, brightness={}, vibrancy={}",
+                new_color, new_density, new_noise, new_line_length, new_line_width, new_view_scale, new_brightness, new_vibrancy);
 
             let mut new_settings = Settings::default();
-            new_settings.color_mode = scheme_to_color_mode(new_color);
+            let prefs = cached_preferences().lock().map(|guard| guard.clone()).unwrap_or_default();
+            new_settings.color_mode = scheme_to_color_mode(new_color, &prefs);
             new_settings.grid_spacing = density_to_grid_spacing(new_density);
             new_settings.noise_multiplier = noise_strength_to_multiplier(new_noise);
             new_settings.line_length = line_length_to_value(new_line_length);
             new_settings.line_width = line_width_to_value(new_line_width);
             new_settings.view_scale = view_scale_to_value(new_view_scale);
             new_settings.brightness_multiplier = brightness_to_multiplier(new_brightness);
+            (new_settings.max_saturation, new_settings.max_lightness, new_settings.base_intensity) = vibrancy_to_caps(new_vibrancy);
+            new_settings.hdr = HDR_ENABLED.load(Ordering::SeqCst);
+            new_settings.color_space = display_p3_to_color_space(DISPLAY_P3_ENABLED.load(Ordering::SeqCst));
             let new_settings = Arc::new(new_settings);
 
             // Check if we have a custom color wheel to inject
@@ -2545,10 +3082,23 @@ async fn run_wallpaper_multi(
                 None
             };
 
+            // Check if a new slideshow or animated image was loaded
+            let color_animation = pending_color_animation().lock().ok().and_then(|mut g| g.take());
+
             for renderer in &mut renderers {
                 // Check if density changed BEFORE updating (update overwrites settings)
                 let density_changed = renderer.flux.grid_spacing() != new_settings.grid_spacing;
 
+                // Switch between SDR and extended-range surfaces
+                let swapchain_format = get_preferred_format(&renderer.capabilities, new_settings.hdr);
+                if swapchain_format != renderer.config.format {
+                    log::info!("Surface format changed to {:?}", swapchain_format);
+                    renderer.config.format = swapchain_format;
+                    renderer.surface.configure(&renderer.device, &renderer.config);
+                    renderer.flux.set_swapchain_format(&renderer.device, &renderer.queue, swapchain_format);
+                }
+                set_surface_color_space(&renderer.window, swapchain_format, new_settings.color_space);
+
                 // Update settings - this handles color, noise, line dimensions, brightness
                 renderer.flux.update(&renderer.device, &renderer.queue, &new_settings);
 
@@ -2578,18 +3128,27 @@ async fn run_wallpaper_multi(
                         mapped_at_creation: false,
                     });
                     renderer.queue.write_buffer(&color_buffer, 0, bytemuck::cast_slice(&wheel));
-                    renderer.flux.lines.update_color_bindings(
+                    renderer.flux.sample_colors_from_buffer(
                         &renderer.device,
                         &renderer.queue,
-                        None,
-                        Some(color_buffer),
+                        color_buffer,
                     );
                     log::info!("Injected custom color wheel into renderer");
                 }
+
+                // Inject slideshow or animated image
+                if let Some(animation) = &color_animation {
+                    renderer.flux.sample_colors_from_animation(
+                        &renderer.device,
+                        &renderer.queue,
+                        animation.clone(),
+                    );
+                    log::info!("Injected color animation into renderer");
+                }
             }
         }
 
-        // Check if screen configuration changed (resolution, display add/remove)
+        // Check if screen configuration changed (resolution, display add/remove, work area)
         if SCREEN_CONFIG_CHANGED.swap(false, Ordering::SeqCst) {
             let new_displays = get_all_displays();
             log::info!("Screen config changed, got {} displays (had {} renderers)",
@@ -2598,53 +3157,64 @@ async fn run_wallpaper_multi(
             // For each renderer, try to match it with updated display info and resize
             for (i, renderer) in renderers.iter_mut().enumerate() {
                 if let Some(display) = new_displays.get(i) {
-                    // Update window position and size
-                    #[cfg(target_os = "macos")]
-                    {
-                        use cocoa::base::id;
-                        use cocoa::foundation::{NSPoint, NSRect, NSSize};
-                        use objc::{msg_send, sel, sel_impl};
-                        use raw_window_handle::{HasWindowHandle, RawWindowHandle};
-
-                        if let Ok(handle) = renderer.window.window_handle() {
-                            if let RawWindowHandle::AppKit(appkit_handle) = handle.as_raw() {
-                                let ns_view: id = appkit_handle.ns_view.as_ptr() as id;
-                                unsafe {
-                                    let ns_window: id = msg_send![ns_view, window];
-                                    let frame_rect = NSRect::new(
-                                        NSPoint::new(display.origin_x, display.origin_y),
-                                        NSSize::new(display.width, display.height),
-                                    );
-                                    let _: () = msg_send![ns_window, setFrame: frame_rect display: cocoa::base::YES];
+                    // Only the work area changes when the menu bar, Dock or taskbar
+                    // moves. Resizing would lay out the lines again, so skip it.
+                    let old = &renderer.display_info;
+                    let frame_changed = (display.origin_x, display.origin_y, display.width, display.height)
+                        != (old.origin_x, old.origin_y, old.width, old.height)
+                        || (display.pixels_wide, display.pixels_high) != (old.pixels_wide, old.pixels_high);
+
+                    if frame_changed {
+                        // Update window position and size
+                        #[cfg(target_os = "macos")]
+                        {
+                            use cocoa::base::id;
+                            use cocoa::foundation::{NSPoint, NSRect, NSSize};
+                            use objc::{msg_send, sel, sel_impl};
+                            use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+
+                            if let Ok(handle) = renderer.window.window_handle() {
+                                if let RawWindowHandle::AppKit(appkit_handle) = handle.as_raw() {
+                                    let ns_view: id = appkit_handle.ns_view.as_ptr() as id;
+                                    unsafe {
+                                        let ns_window: id = msg_send![ns_view, window];
+                                        let frame_rect = NSRect::new(
+                                            NSPoint::new(display.origin_x, display.origin_y),
+                                            NSSize::new(display.width, display.height),
+                                        );
+                                        let _: () = msg_send![ns_window, setFrame: frame_rect display: cocoa::base::YES];
+                                    }
                                 }
                             }
                         }
-                    }
-
-                    // Get the new physical size from the window
-                    let new_physical_size = renderer.window.inner_size();
-                    let _scale = renderer.window.scale_factor();
-                    let logical_width = display.width as u32;
-                    let logical_height = display.height as u32;
 
-                    log::info!("Display {}: updating to {}x{} logical, {}x{} physical",
-                        i, logical_width, logical_height,
-                        new_physical_size.width, new_physical_size.height);
+                        // Get the new physical size from the window
+                        let new_physical_size = renderer.window.inner_size();
+                        let _scale = renderer.window.scale_factor();
+                        let logical_width = display.width as u32;
+                        let logical_height = display.height as u32;
+
+                        log::info!("Display {}: updating to {}x{} logical, {}x{} physical",
+                            i, logical_width, logical_height,
+                            new_physical_size.width, new_physical_size.height);
+
+                        // Reconfigure surface
+                        renderer.config.width = new_physical_size.width.max(1);
+                        renderer.config.height = new_physical_size.height.max(1);
+                        renderer.surface.configure(&renderer.device, &renderer.config);
+
+                        // Resize flux renderer
+                        renderer.flux.resize(
+                            &renderer.device,
+                            &renderer.queue,
+                            logical_width,
+                            logical_height,
+                            new_physical_size.width,
+                            new_physical_size.height,
+                        );
+                    }
 
-                    // Reconfigure surface
-                    renderer.config.width = new_physical_size.width.max(1);
-                    renderer.config.height = new_physical_size.height.max(1);
-                    renderer.surface.configure(&renderer.device, &renderer.config);
-
-                    // Resize flux renderer
-                    renderer.flux.resize(
-                        &renderer.device,
-                        &renderer.queue,
-                        logical_width,
-                        logical_height,
-                        new_physical_size.width,
-                        new_physical_size.height,
-                    );
+                    renderer.flux.set_safe_zones(display.safe_zones);
 
                     // Update stored display info
                     renderer.display_info = display.clone();
@@ -2783,7 +3353,7 @@ async fn run_wallpaper(
         .expect("Failed to create device");
 
     let swapchain_capabilities = window_surface.get_capabilities(&adapter);
-    let swapchain_format = get_preferred_format(&swapchain_capabilities);
+    let swapchain_format = get_preferred_format(&swapchain_capabilities, false);
 
     // Use display dimensions directly rather than relying on inner_size()
     // This ensures we use the correct size even when NSWindow frame differs from winit's view
@@ -2798,7 +3368,9 @@ async fn run_wallpaper(
     log::info!("Window scale_factor: {}", scale_factor);
 
     let mut config = wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        // Frames are copied to crossfade from when the lines are laid out again
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | (swapchain_capabilities.usages & wgpu::TextureUsages::COPY_SRC),
         format: swapchain_format,
         width: physical_width.max(1),
         height: physical_height.max(1),
@@ -2811,7 +3383,7 @@ async fn run_wallpaper(
     window_surface.configure(&device, &config);
 
     let settings = Arc::new(Settings::default());
-    let flux = Flux::new(
+    let mut flux = Flux::new(
         &device,
         &command_queue,
         swapchain_format,
@@ -2822,6 +3394,7 @@ async fn run_wallpaper(
         &Arc::clone(&settings),
     )
     .unwrap();
+    flux.set_safe_zones(display.safe_zones);
 
     window.set_visible(true);
 
@@ -2833,6 +3406,7 @@ async fn run_wallpaper(
         flux,
         settings,
         color_image: Arc::new(Mutex::new(None)),
+        color_animation: Arc::new(Mutex::new(None)),
     };
 
     let start = std::time::Instant::now();
@@ -2932,11 +3506,13 @@ async fn run_normal(
         .expect("Failed to create device");
 
     let swapchain_capabilities = window_surface.get_capabilities(&adapter);
-    let swapchain_format = get_preferred_format(&swapchain_capabilities);
+    let swapchain_format = get_preferred_format(&swapchain_capabilities, false);
 
     let physical_size = window.inner_size();
     let mut config = wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        // Frames are copied to crossfade from when the lines are laid out again
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | (swapchain_capabilities.usages & wgpu::TextureUsages::COPY_SRC),
         format: swapchain_format,
         width: physical_size.width,
         height: physical_size.height,
@@ -2972,6 +3548,7 @@ async fn run_normal(
         flux,
         settings,
         color_image: Arc::new(Mutex::new(None)),
+        color_animation: Arc::new(Mutex::new(None)),
     };
 
     let start = std::time::Instant::now();
@@ -3002,8 +3579,12 @@ async fn run_normal(
                     ..
                 } => elwt.exit(),
                 WindowEvent::DroppedFile(path) => {
-                    let bytes = std::fs::read(path).unwrap();
-                    app.decode_image(bytes);
+                    if path.is_dir() {
+                        app.load_slideshow(path);
+                    } else {
+                        let bytes = std::fs::read(path).unwrap();
+                        app.decode_image(bytes);
+                    }
                     window.request_redraw();
                 }
                 WindowEvent::Resized(new_size) => {
@@ -3037,7 +3618,13 @@ async fn run_normal(
     }).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
 }
 
-fn get_preferred_format(capabilities: &wgpu::SurfaceCapabilities) -> wgpu::TextureFormat {
+/// Pick a surface format. With HDR enabled, prefer a float surface, which
+/// presents with extended range where the compositor supports it.
+fn get_preferred_format(capabilities: &wgpu::SurfaceCapabilities, hdr: bool) -> wgpu::TextureFormat {
+    if hdr && capabilities.formats.contains(&wgpu::TextureFormat::Rgba16Float) {
+        return wgpu::TextureFormat::Rgba16Float;
+    }
+
     let preferred_formats = [
         wgpu::TextureFormat::Rgb10a2Unorm,
         wgpu::TextureFormat::Bgra8Unorm,
@@ -3054,3 +3641,74 @@ fn get_preferred_format(capabilities: &wgpu::SurfaceCapabilities) -> wgpu::Textu
 
     capabilities.formats[0]
 }
+
+/// Tag the Metal layer behind a window with the color space we render in, so
+/// the compositor can convert it for the display. Float surfaces also opt in
+/// to extended dynamic range, or macOS clamps them to SDR.
+#[cfg(target_os = "macos")]
+fn set_surface_color_space(window: &Window, format: wgpu::TextureFormat, color_space: flux::settings::ColorSpace) {
+    use cocoa::base::{id, nil, NO, YES};
+    use objc::{class, msg_send, sel, sel_impl};
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    use std::ffi::c_void;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        static kCGColorSpaceSRGB: *const c_void;
+        static kCGColorSpaceDisplayP3: *const c_void;
+        static kCGColorSpaceExtendedLinearSRGB: *const c_void;
+        static kCGColorSpaceExtendedLinearDisplayP3: *const c_void;
+        fn CGColorSpaceCreateWithName(name: *const c_void) -> *mut c_void;
+        fn CGColorSpaceRelease(space: *mut c_void);
+    }
+
+    let Ok(handle) = window.window_handle() else {
+        return;
+    };
+    let RawWindowHandle::AppKit(appkit_handle) = handle.as_raw() else {
+        return;
+    };
+
+    unsafe {
+        // Float surfaces hold linear, extended-range values
+        let is_extended_range = matches!(format, wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgba32Float);
+        let name = match (color_space, is_extended_range) {
+            (flux::settings::ColorSpace::Srgb, false) => kCGColorSpaceSRGB,
+            (flux::settings::ColorSpace::Srgb, true) => kCGColorSpaceExtendedLinearSRGB,
+            (flux::settings::ColorSpace::DisplayP3, false) => kCGColorSpaceDisplayP3,
+            (flux::settings::ColorSpace::DisplayP3, true) => kCGColorSpaceExtendedLinearDisplayP3,
+        };
+        let space = CGColorSpaceCreateWithName(name);
+        if space.is_null() {
+            return;
+        }
+
+        // wgpu renders into either the view's root layer or a sublayer of it
+        let ns_view: id = appkit_handle.ns_view.as_ptr() as id;
+        let root_layer: id = msg_send![ns_view, layer];
+        if root_layer != nil {
+            let sublayers: id = msg_send![root_layer, sublayers];
+            let count: u64 = if sublayers != nil { msg_send![sublayers, count] } else { 0 };
+            let layers = std::iter::once(root_layer)
+                .chain((0..count).map(|i| -> id { msg_send![sublayers, objectAtIndex: i] }));
+            for layer in layers {
+                let is_metal_layer: objc::runtime::BOOL = msg_send![layer, isKindOfClass: class!(CAMetalLayer)];
+                if is_metal_layer == YES {
+                    let _: () = msg_send![layer, setColorspace: space];
+                    let _: () = msg_send![layer, setWantsExtendedDynamicRangeContent: if is_extended_range { YES } else { NO }];
+                }
+            }
+        }
+
+        CGColorSpaceRelease(space);
+    }
+}
+
+/// Other platforms can't tag the surface, so the compositor always treats it
+/// as sRGB.
+#[cfg(not(target_os = "macos"))]
+fn set_surface_color_space(_window: &Window, _format: wgpu::TextureFormat, color_space: flux::settings::ColorSpace) {
+    if color_space != flux::settings::ColorSpace::Srgb {
+        log::warn!("The {:?} color space is only supported on macOS. Colors will be shown as sRGB.", color_space);
+    }
+}
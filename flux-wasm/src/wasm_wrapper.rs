@@ -11,6 +11,7 @@ use winit::platform::web::WindowBuilderExtWebSys;
 #[wasm_bindgen]
 pub struct Flux {
     canvas: Canvas,
+    adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
     #[allow(dead_code)]
@@ -26,7 +27,9 @@ pub struct Flux {
 impl Flux {
     #[wasm_bindgen(setter)]
     pub fn set_settings(&mut self, settings_object: &JsValue) {
-        let settings: settings::Settings = settings_object.into_serde().unwrap();
+        let mut settings: settings::Settings = settings_object.into_serde().unwrap();
+        settings.antialiasing =
+            flux::render::msaa::fallback_if_unsupported(&self.adapter, settings.antialiasing);
         self.instance
             .update(&self.device, &self.queue, &Arc::new(settings));
     }
@@ -53,7 +56,7 @@ impl Flux {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
             view_formats: &[],
             usage: wgpu::TextureUsages::COPY_DST
                 | wgpu::TextureUsages::TEXTURE_BINDING
@@ -115,8 +118,8 @@ impl Flux {
 
         let canvas = Canvas::new(html_canvas);
 
-        let settings = match settings_object.into_serde() {
-            Ok(settings) => Arc::new(settings),
+        let mut settings: settings::Settings = match settings_object.into_serde() {
+            Ok(settings) => settings,
             Err(msg) => return Err(JsValue::from_str(&msg.to_string())),
         };
 
@@ -138,6 +141,10 @@ impl Flux {
 
         log::debug!("{:?}\n{:?}", adapter.get_info(), adapter.features(),);
 
+        settings.antialiasing =
+            flux::render::msaa::fallback_if_unsupported(&adapter, settings.antialiasing);
+        let settings = Arc::new(settings);
+
         // Make sure we use the texture resolution limits from the adapter, so we can support images the size of the swapchain.
         let limits = wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits());
 
@@ -161,7 +168,9 @@ impl Flux {
         log::debug!("Swapchain format: {:?}", swapchain_format);
 
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // Frames are copied to crossfade from when the lines are laid out again
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (swapchain_capabilities.usages & wgpu::TextureUsages::COPY_SRC),
             format: swapchain_format,
             width: physical_width,
             height: physical_height,
@@ -188,6 +197,7 @@ impl Flux {
         Ok(Self {
             instance: flux,
             canvas,
+            adapter,
             device,
             queue,
             window,
@@ -0,0 +1,105 @@
+struct BloomUniforms {
+  threshold: f32,
+  knee: f32,
+  radius: f32,
+  intensity: f32,
+}
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: BloomUniforms;
+@group(0) @binding(1) var source_sampler: sampler;
+@group(0) @binding(2) var source_texture: texture_2d<f32>;
+
+// Draw a single triangle that covers the screen
+@vertex
+fn main_vs(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+  let xy = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+  return VertexOutput(
+    vec4<f32>(2.0 * xy - 1.0, 0.0, 1.0),
+    vec2<f32>(xy.x, 1.0 - xy.y),
+  );
+}
+
+fn sample_offset(uv: vec2<f32>, texel: vec2<f32>, x: f32, y: f32) -> vec3<f32> {
+  return textureSample(source_texture, source_sampler, uv + texel * vec2<f32>(x, y)).rgb;
+}
+
+// The 13-tap downsample filter from Jimenez's "Next Generation Post
+// Processing in Call of Duty: Advanced Warfare". Bilinear taps in overlapping
+// boxes keep thin lines from flickering as they move across texels.
+fn downsample_13(uv: vec2<f32>) -> vec3<f32> {
+  let texel = 1.0 / vec2<f32>(textureDimensions(source_texture));
+
+  let a = sample_offset(uv, texel, -2.0, -2.0);
+  let b = sample_offset(uv, texel, 0.0, -2.0);
+  let c = sample_offset(uv, texel, 2.0, -2.0);
+  let d = sample_offset(uv, texel, -2.0, 0.0);
+  let e = sample_offset(uv, texel, 0.0, 0.0);
+  let f = sample_offset(uv, texel, 2.0, 0.0);
+  let g = sample_offset(uv, texel, -2.0, 2.0);
+  let h = sample_offset(uv, texel, 0.0, 2.0);
+  let i = sample_offset(uv, texel, 2.0, 2.0);
+  let j = sample_offset(uv, texel, -1.0, -1.0);
+  let k = sample_offset(uv, texel, 1.0, -1.0);
+  let l = sample_offset(uv, texel, -1.0, 1.0);
+  let m = sample_offset(uv, texel, 1.0, 1.0);
+
+  return e * 0.125
+    + (a + c + g + i) * 0.03125
+    + (b + d + f + h) * 0.0625
+    + (j + k + l + m) * 0.125;
+}
+
+// A 3x3 tent filter for smooth upsampling
+fn upsample_tent(uv: vec2<f32>) -> vec3<f32> {
+  let texel = 1.0 / vec2<f32>(textureDimensions(source_texture));
+
+  return (
+    sample_offset(uv, texel, -1.0, -1.0)
+    + 2.0 * sample_offset(uv, texel, 0.0, -1.0)
+    + sample_offset(uv, texel, 1.0, -1.0)
+    + 2.0 * sample_offset(uv, texel, -1.0, 0.0)
+    + 4.0 * sample_offset(uv, texel, 0.0, 0.0)
+    + 2.0 * sample_offset(uv, texel, 1.0, 0.0)
+    + sample_offset(uv, texel, -1.0, 1.0)
+    + 2.0 * sample_offset(uv, texel, 0.0, 1.0)
+    + sample_offset(uv, texel, 1.0, 1.0)
+  ) / 16.0;
+}
+
+// Keep the parts of the scene above the threshold, easing in over the knee
+fn soft_threshold(rgb: vec3<f32>) -> vec3<f32> {
+  let brightness = max(max(rgb.r, rgb.g), rgb.b);
+  var soft = clamp(brightness - uniforms.threshold + uniforms.knee, 0.0, 2.0 * uniforms.knee);
+  soft = soft * soft / (4.0 * uniforms.knee + 1e-5);
+  let contribution = max(soft, brightness - uniforms.threshold) / max(brightness, 1e-5);
+  return rgb * contribution;
+}
+
+@fragment
+fn prefilter(fs_input: VertexOutput) -> @location(0) vec4<f32> {
+  let color = downsample_13(fs_input.uv);
+  return vec4<f32>(soft_threshold(max(color, vec3<f32>(0.0))), 1.0);
+}
+
+@fragment
+fn downsample(fs_input: VertexOutput) -> @location(0) vec4<f32> {
+  return vec4<f32>(downsample_13(fs_input.uv), 1.0);
+}
+
+// Blended additively onto the next level up. The radius controls how much of
+// the wider, coarser levels makes it into the final glow.
+@fragment
+fn upsample(fs_input: VertexOutput) -> @location(0) vec4<f32> {
+  return vec4<f32>(uniforms.radius * upsample_tent(fs_input.uv), 1.0);
+}
+
+// Blended additively onto the scene
+@fragment
+fn composite(fs_input: VertexOutput) -> @location(0) vec4<f32> {
+  return vec4<f32>(uniforms.intensity * upsample_tent(fs_input.uv), 1.0);
+}
@@ -0,0 +1,294 @@
+struct CompositeUniforms {
+  color_space: u32,
+  linear_output: u32,
+  dither_scale: f32,
+  film_grain: f32,
+  frame: u32,
+  sharpness: f32,
+  kaleidoscope: u32,
+  vignette: f32,
+  safe_zone_dimming: f32,
+  // Top, bottom, left and right
+  safe_zones: vec4<f32>,
+}
+
+struct ExposureUniforms {
+  target_luminance: f32,
+  highlight_percentile: f32,
+  adaptation_rate: f32,
+  min_exposure: f32,
+  max_exposure: f32,
+  delta_time: f32,
+  auto_exposure: u32,
+  exposure: f32,
+  tone_mapping: u32,
+  headroom: f32,
+}
+
+struct BackgroundUniforms {
+  kind: u32,
+  blend: u32,
+  color_a: vec4<f32>,
+  color_b: vec4<f32>,
+}
+
+struct Exposure {
+  exposure: f32,
+  average_luminance: f32,
+  highlight_luminance: f32,
+}
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: CompositeUniforms;
+@group(0) @binding(1) var scene_sampler: sampler;
+@group(0) @binding(2) var scene_texture: texture_2d<f32>;
+@group(0) @binding(3) var blue_noise_texture: texture_2d<f32>;
+
+const BLUE_NOISE_SIZE = 64u;
+
+const pi = 3.141592653589793;
+const tau = 2.0 * pi;
+
+@group(1) @binding(0) var<uniform> exposure_uniforms: ExposureUniforms;
+@group(1) @binding(1) var<storage, read> exposure: Exposure;
+
+@group(2) @binding(0) var<uniform> background: BackgroundUniforms;
+@group(2) @binding(1) var background_sampler: sampler;
+@group(2) @binding(2) var background_texture: texture_2d<f32>;
+
+// Draw a single triangle that covers the screen
+@vertex
+fn main_vs(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+  let xy = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+  return VertexOutput(
+    vec4<f32>(2.0 * xy - 1.0, 0.0, 1.0),
+    vec2<f32>(xy.x, 1.0 - xy.y),
+  );
+}
+
+// Linear sRGB to linear Display P3. The columns are the sRGB primaries in P3.
+const SRGB_TO_DISPLAY_P3 = mat3x3<f32>(
+  vec3<f32>(0.8225, 0.0332, 0.0171),
+  vec3<f32>(0.1774, 0.9669, 0.0724),
+  vec3<f32>(0.0000, 0.0000, 0.9108),
+);
+
+// Encode linear RGB with the sRGB transfer function
+fn linear_to_srgb(rgb: vec3<f32>) -> vec3<f32> {
+  let low = rgb * 12.92;
+  let high = 1.055 * pow(rgb, vec3<f32>(1.0 / 2.4)) - 0.055;
+  return select(high, low, rgb <= vec3<f32>(0.0031308));
+}
+
+// Decode sRGB-encoded RGB to linear
+fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
+  let low = srgb / 12.92;
+  let high = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+  return select(high, low, srgb <= vec3<f32>(0.04045));
+}
+
+// Look up the tiled blue noise, stepping the tile by a different amount each
+// frame
+fn blue_noise(pixel: vec2<u32>, step: vec2<u32>) -> f32 {
+  let offset = uniforms.frame * step;
+  return textureLoad(blue_noise_texture, (pixel + offset) % BLUE_NOISE_SIZE, 0).r;
+}
+
+// Dither and add grain to encoded colors, where the output is quantized
+fn add_noise(encoded: vec3<f32>, pixel: vec2<u32>) -> vec3<f32> {
+  // Reshape the uniform noise into a triangular distribution over [-1, 1],
+  // which keeps the noise level constant across the range
+  let n = 2.0 * blue_noise(pixel, vec2<u32>(29u, 47u)) - 1.0;
+  let dither = sign(n) * (1.0 - sqrt(1.0 - abs(n)));
+
+  let grain = blue_noise(pixel + BLUE_NOISE_SIZE / 2u, vec2<u32>(13u, 41u)) - 0.5;
+
+  let noise = uniforms.dither_scale * dither + uniforms.film_grain * grain;
+  return max(encoded + noise, vec3<f32>(0.0));
+}
+
+// Get the linear background color at a point on the screen
+fn get_background(uv: vec2<f32>) -> vec3<f32> {
+  switch background.kind {
+    case 1u: {
+      return mix(background.color_a.rgb, background.color_b.rgb, uv.y);
+    }
+    case 2u: {
+      // Reach the edge color at the corners
+      let screen_size = vec2<f32>(textureDimensions(scene_texture));
+      let aspect = screen_size.x / screen_size.y;
+      let offset = (uv - 0.5) * vec2<f32>(aspect, 1.0);
+      let corner = length(vec2<f32>(0.5 * aspect, 0.5));
+      return mix(background.color_a.rgb, background.color_b.rgb, length(offset) / corner);
+    }
+    case 3u: {
+      // Scale the image to cover the screen, cropping the overflow
+      let screen_size = vec2<f32>(textureDimensions(scene_texture));
+      let image_size = vec2<f32>(textureDimensions(background_texture));
+      let scale = image_size / screen_size;
+      let fit = scale / min(scale.x, scale.y);
+      let image_uv = (uv - 0.5) / fit + 0.5;
+      return textureSampleLevel(background_texture, background_sampler, image_uv, 0.0).rgb;
+    }
+    case 0u, default: {
+      return background.color_a.rgb;
+    }
+  }
+}
+
+// Composite the lines over the background
+fn blend_background(lines: vec3<f32>, bg: vec3<f32>) -> vec3<f32> {
+  switch background.blend {
+    case 1u: {
+      return lines + bg - lines * min(bg, vec3<f32>(1.0));
+    }
+    case 2u: {
+      return max(lines, bg);
+    }
+    case 0u, default: {
+      return lines + bg;
+    }
+  }
+}
+
+// Sample the scene, sharpening it if it's being upscaled. The sharpened
+// color is clamped to the neighbourhood, so edges don't ring.
+fn sample_scene(uv: vec2<f32>) -> vec3<f32> {
+  let center = textureSample(scene_texture, scene_sampler, uv).rgb;
+  if (uniforms.sharpness <= 0.0) {
+    return center;
+  }
+
+  let texel = 1.0 / vec2<f32>(textureDimensions(scene_texture));
+  let up = textureSample(scene_texture, scene_sampler, uv - vec2<f32>(0.0, texel.y)).rgb;
+  let down = textureSample(scene_texture, scene_sampler, uv + vec2<f32>(0.0, texel.y)).rgb;
+  let left = textureSample(scene_texture, scene_sampler, uv - vec2<f32>(texel.x, 0.0)).rgb;
+  let right = textureSample(scene_texture, scene_sampler, uv + vec2<f32>(texel.x, 0.0)).rgb;
+
+  let blurred = 0.25 * (up + down + left + right);
+  let sharpened = center + uniforms.sharpness * (center - blurred);
+  let lower = min(center, min(min(up, down), min(left, right)));
+  let upper = max(center, max(max(up, down), max(left, right)));
+  return clamp(sharpened, lower, upper);
+}
+
+// Fold the screen into mirrored wedges around the center, all showing the
+// same slice of the scene. The slice is taken from within the inscribed circle,
+// so the corners never reach past the edge of the scene.
+fn kaleidoscope(uv: vec2<f32>) -> vec2<f32> {
+  if (uniforms.kaleidoscope == 0u) {
+    return uv;
+  }
+
+  let screen_size = vec2<f32>(textureDimensions(scene_texture));
+  let aspect = screen_size.x / screen_size.y;
+  let offset = (uv - 0.5) * vec2<f32>(aspect, 1.0);
+
+  // Reflect every other half wedge, so that neighbours meet seamlessly
+  let wedge = tau / f32(uniforms.kaleidoscope);
+  let angle = atan2(offset.y, offset.x);
+  var folded_angle = angle - wedge * floor(angle / wedge);
+  folded_angle = min(folded_angle, wedge - folded_angle);
+
+  let corner = length(vec2<f32>(0.5 * aspect, 0.5));
+  let radius = length(offset) * 0.5 * min(aspect, 1.0) / corner;
+  let folded = radius * vec2<f32>(cos(folded_angle), sin(folded_angle));
+  return folded / vec2<f32>(aspect, 1.0) + 0.5;
+}
+
+// How much of a safe zone of the given size covers a point at some distance
+// from its edge. Fades out past the zone, so the dimming has no hard edge.
+fn edge_coverage(distance: f32, size: f32) -> f32 {
+  if (size <= 0.0) {
+    return 0.0;
+  }
+  return 1.0 - smoothstep(size, 1.5 * size, distance);
+}
+
+// Dim the lines behind system UI, like a menu bar or taskbar
+fn safe_zone_dimming(uv: vec2<f32>) -> f32 {
+  if (uniforms.safe_zone_dimming <= 0.0) {
+    return 1.0;
+  }
+
+  let zones = uniforms.safe_zones;
+  let coverage = max(
+    max(edge_coverage(uv.y, zones.x), edge_coverage(1.0 - uv.y, zones.y)),
+    max(edge_coverage(uv.x, zones.z), edge_coverage(1.0 - uv.x, zones.w)),
+  );
+  return 1.0 - uniforms.safe_zone_dimming * coverage;
+}
+
+// Darken towards the corners of the screen
+fn vignette(uv: vec2<f32>) -> f32 {
+  let distance = length(uv - 0.5) / length(vec2<f32>(0.5));
+  return 1.0 - uniforms.vignette * smoothstep(0.3, 1.0, distance);
+}
+
+fn reinhard(rgb: vec3<f32>) -> vec3<f32> {
+  return rgb / (1.0 + rgb);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+fn aces(rgb: vec3<f32>) -> vec3<f32> {
+  return saturate((rgb * (2.51 * rgb + 0.03)) / (rgb * (2.43 * rgb + 0.59) + 0.14));
+}
+
+// Map [0, inf) onto [0, 1]
+fn tone_map(rgb: vec3<f32>) -> vec3<f32> {
+  switch exposure_uniforms.tone_mapping {
+    case 1u: {
+      return reinhard(rgb);
+    }
+    case 2u: {
+      return aces(rgb);
+    }
+    case 0u, default: {
+      return min(rgb, vec3<f32>(1.0));
+    }
+  }
+}
+
+@fragment
+fn main_fs(fs_input: VertexOutput) -> @location(0) vec4<f32> {
+  // The scene holds linear colors
+  var color = sample_scene(kaleidoscope(fs_input.uv));
+  color *= exposure_uniforms.exposure;
+  if (exposure_uniforms.auto_exposure == 1u) {
+    color *= exposure.exposure;
+  }
+
+  // The background isn't exposed or tone mapped, so it keeps its colors
+  var bg = get_background(fs_input.uv);
+
+  if (uniforms.color_space == 1u) {
+    color = SRGB_TO_DISPLAY_P3 * color;
+    bg = SRGB_TO_DISPLAY_P3 * bg;
+  }
+
+  // Extended-range surfaces can show values up to the headroom
+  let headroom = exposure_uniforms.headroom;
+  color = headroom * tone_map(max(color, vec3<f32>(0.0)) / headroom);
+  // Zones and the vignette follow the screen, not the folded scene
+  color *= safe_zone_dimming(fs_input.uv);
+  color = blend_background(color, bg);
+  color *= vignette(fs_input.uv);
+
+  // Display P3 shares the sRGB transfer function
+  let pixel = vec2<u32>(fs_input.position.xy);
+  let has_noise = uniforms.dither_scale > 0.0 || uniforms.film_grain > 0.0;
+  if (uniforms.linear_output == 0u) {
+    color = linear_to_srgb(saturate(color));
+    if (has_noise) {
+      color = add_noise(color, pixel);
+    }
+  } else if (has_noise) {
+    // The target encodes on write, so round-trip through the encoded space
+    color = srgb_to_linear(add_noise(linear_to_srgb(max(color, vec3<f32>(0.0))), pixel));
+  }
+  return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,104 @@
+struct DyeUniforms {
+  inverse_view_matrix: mat4x4<f32>,
+  palette: array<vec4<f32>, 6>,
+  palette_size: u32,
+  linear_output: u32,
+  delta_time: f32,
+  flow_speed: f32,
+  dissipation: f32,
+  injection: f32,
+  brightness: f32,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: DyeUniforms;
+@group(0) @binding(1) var linear_sampler: sampler;
+
+// Linear colors
+@group(1) @binding(0) var dye_texture: texture_2d<f32>;
+
+@group(2) @binding(0) var out_dye_texture: texture_storage_2d<rgba16float, write>;
+
+@group(3) @binding(0) var velocity_texture: texture_2d<f32>;
+
+const pi = 3.141592653589793;
+const tau = 2.0 * pi;
+
+@compute
+@workgroup_size(16, 16, 1)
+fn advect(
+  @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+  let size = textureDimensions(out_dye_texture);
+  if (any(global_id.xy >= size)) {
+    return;
+  }
+
+  let position = (vec2<f32>(global_id.xy) + 0.5) / vec2<f32>(size);
+  let velocity = textureSampleLevel(velocity_texture, linear_sampler, position, 0.0).xy;
+
+  // Trace back along the flow, like advect.comp.wgsl does for the velocity
+  let advected_position = position - uniforms.delta_time * uniforms.flow_speed * velocity;
+  let decay = 1.0 + uniforms.dissipation * uniforms.delta_time;
+  let dye = textureSampleLevel(dye_texture, linear_sampler, advected_position, 0.0).rgb / decay;
+
+  // Faster parts of the fluid pick up more dye
+  let amount = uniforms.injection * uniforms.delta_time * saturate(2.5 * length(velocity));
+  let color = srgb_to_linear(get_color(velocity));
+
+  textureStore(out_dye_texture, global_id.xy, vec4<f32>(dye + amount * color, 1.0));
+}
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+}
+
+// Draw a single triangle that covers the screen
+@vertex
+fn main_vs(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+  let xy = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+  let clip_position = vec4<f32>(2.0 * xy - 1.0, 0.0, 1.0);
+
+  // Undo the view transform to find which part of the dye is on screen
+  let canvas_position = uniforms.inverse_view_matrix * clip_position;
+
+  var out: VertexOutput;
+  out.position = clip_position;
+  out.uv = 0.5 * canvas_position.xy / canvas_position.w + 0.5;
+  return out;
+}
+
+@fragment
+fn main_fs(in: VertexOutput) -> @location(0) vec4<f32> {
+  var color = uniforms.brightness * textureSampleLevel(dye_texture, linear_sampler, in.uv, 0.0).rgb;
+  if (uniforms.linear_output == 0u) {
+    color = linear_to_srgb(saturate(color));
+  }
+  return vec4<f32>(color, 1.0);
+}
+
+// Pick a color from the palette by the direction of the flow, or derive one
+// from the velocity without a palette.
+fn get_color(velocity: vec2<f32>) -> vec3<f32> {
+  let size = uniforms.palette_size;
+  if (size == 0u) {
+    return vec3<f32>(saturate(vec2<f32>(1.0, 0.66) * (0.5 + velocity)), 0.5);
+  }
+
+  let raw_index = (atan2(velocity.y, velocity.x) + pi) / tau * f32(size);
+  let index = u32(floor(raw_index)) % size;
+  let next_index = (index + 1u) % size;
+  return mix(uniforms.palette[index], uniforms.palette[next_index], fract(raw_index)).rgb;
+}
+
+fn linear_to_srgb(rgb: vec3<f32>) -> vec3<f32> {
+  let low = rgb * 12.92;
+  let high = 1.055 * pow(rgb, vec3<f32>(1.0 / 2.4)) - 0.055;
+  return select(high, low, rgb <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
+  let low = srgb / 12.92;
+  let high = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+  return select(high, low, srgb <= vec3<f32>(0.04045));
+}
@@ -12,11 +12,28 @@ struct LineUniforms {
   color_mode: u32,
   delta_time: f32,
   brightness_scale: f32,
+  max_saturation: f32,
+  max_lightness: f32,
+  base_intensity: f32,
+  linear_output: u32,
+  line_curvature: f32,
+  line_taper: f32,
+  color_mapping: u32,
 }
 
 @group(0) @binding(0) var<uniform> uniforms: LineUniforms;
 @group(1) @binding(0) var<uniform> view_matrix: mat4x4<f32>;
 
+struct EndpointUniforms {
+  style: u32,
+  size: f32,
+  opacity: f32,
+}
+
+@group(2) @binding(0) var<uniform> endpoint_uniforms: EndpointUniforms;
+@group(2) @binding(1) var sprite_sampler: sampler;
+@group(2) @binding(2) var sprite_texture: texture_2d<f32>;
+
 struct VertexOutput {
   @builtin(position) f_position: vec4<f32>,
   @location(0) f_vertex: vec2<f32>,
@@ -39,7 +56,7 @@ fn main_vs(
   var point
     = vec2<f32>(uniforms.aspect, 1.0) * uniforms.zoom * (basepoint * 2.0 - 1.0)
     + endpoint
-    + 0.5 * uniforms.line_width * width * vertex;
+    + 0.5 * uniforms.line_width * width * endpoint_uniforms.size * vertex;
 
   point.x /= uniforms.aspect;
 
@@ -137,36 +154,87 @@ fn hsl_to_rgb(hsl: vec3<f32>) -> vec3<f32> {
   return rgb + m;
 }
 
+// Encode linear RGB with the sRGB transfer function
+fn linear_to_srgb(rgb: vec3<f32>) -> vec3<f32> {
+  let low = rgb * 12.92;
+  let high = 1.055 * pow(rgb, vec3<f32>(1.0 / 2.4)) - 0.055;
+  return select(high, low, rgb <= vec3<f32>(0.0031308));
+}
+
+// Decode sRGB-encoded RGB to linear
+fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
+  let low = srgb / 12.92;
+  let high = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+  return select(high, low, srgb <= vec3<f32>(0.04045));
+}
+
 // Cap saturation and luminance to reduce eye strain
 fn cap_brightness(rgb: vec3<f32>) -> vec3<f32> {
   var hsl = rgb_to_hsl(rgb);
-  // Cap saturation at 25% for very muted colors
-  hsl.y = min(hsl.y, 0.25);
-  // Cap luminance at 30% - much darker
-  hsl.z = min(hsl.z, 0.30);
+  hsl.y = min(hsl.y, uniforms.max_saturation);
+  hsl.z = min(hsl.z, uniforms.max_lightness);
   return hsl_to_rgb(hsl);
 }
 
 @fragment
 fn main_fs(fs_input: VertexOutput) -> @location(0) vec4<f32> {
-  var color = fs_input.f_bottom_color;
+  var color = fs_input.f_top_color;
+  var tint = vec3<f32>(1.0);
+  let distance = length(fs_input.f_vertex);
+  var shape = 1.0;
 
-  // Test which side of the endpoint we're on.
-  let side
-    = (fs_input.f_vertex.x - fs_input.f_mindpoint_vector.x) * (-fs_input.f_mindpoint_vector.y)
-    - (fs_input.f_vertex.y - fs_input.f_mindpoint_vector.y) * (-fs_input.f_mindpoint_vector.x);
+  switch endpoint_uniforms.style {
+    // Disc
+    case 0u, default: {
+      // Test which side of the endpoint we're on.
+      let side
+        = (fs_input.f_vertex.x - fs_input.f_mindpoint_vector.x) * (-fs_input.f_mindpoint_vector.y)
+        - (fs_input.f_vertex.y - fs_input.f_mindpoint_vector.y) * (-fs_input.f_mindpoint_vector.x);
 
-  if (side > 0.0) {
-    color = fs_input.f_top_color;
-  }
+      if (side <= 0.0) {
+        color = fs_input.f_bottom_color;
+      }
 
-  let distance = length(fs_input.f_vertex);
-  let smoothEdges = 1.0 - smoothstep(1.0 - fwidth(distance), 1.0, distance);
+      shape = 1.0 - smoothstep(1.0 - fwidth(distance), 1.0, distance);
+    }
+
+    // Glow
+    case 1u: {
+      let falloff = saturate(1.0 - distance);
+      shape = falloff * falloff;
+    }
+
+    // Ring
+    case 2u: {
+      let ring_distance = abs(distance - 0.8);
+      shape = 1.0 - smoothstep(0.2 - fwidth(distance), 0.2, ring_distance);
+    }
+
+    // Sprite
+    case 3u: {
+      let uv = vec2<f32>(0.5, -0.5) * fs_input.f_vertex + 0.5;
+      // The sprite is decoded on sampling. Encode it to match the line colors.
+      let sprite = textureSample(sprite_texture, sprite_sampler, uv);
+      tint = linear_to_srgb(saturate(sprite.rgb));
+      shape = sprite.a;
+    }
+  }
 
   // Apply brightness capping
   let capped_color = cap_brightness(color.rgb);
   // Scale color by brightness_scale (based on line count) to normalize across displays
-  // Base multiplier 0.3 for darker overall look, then scale by line count
-  let scaled_color = capped_color * uniforms.brightness_scale * 0.3;
-  return vec4<f32>(scaled_color, color.a * smoothEdges);
+  // Base intensity for a darker overall look, then scale by line count
+  let scaled_color = capped_color * tint * uniforms.brightness_scale * uniforms.base_intensity;
+  // Premultiply, so that every blend mode can be expressed with fixed factors
+  let alpha = color.a * shape * endpoint_uniforms.opacity;
+  return vec4<f32>(encode_output(scaled_color * alpha), alpha);
+}
+
+// Decode the final premultiplied color, once, for targets that expect linear
+// colors. See line.wgsl.
+fn encode_output(premultiplied: vec3<f32>) -> vec3<f32> {
+  if (uniforms.linear_output == 1u) {
+    return srgb_to_linear(premultiplied);
+  }
+  return premultiplied;
 }
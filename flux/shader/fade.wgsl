@@ -0,0 +1,25 @@
+@group(0) @binding(0) var snapshot_sampler: sampler;
+@group(0) @binding(1) var snapshot_texture: texture_2d<f32>;
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+}
+
+// Draw a single triangle that covers the screen
+@vertex
+fn main_vs(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+  let xy = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+  return VertexOutput(
+    vec4<f32>(2.0 * xy - 1.0, 0.0, 1.0),
+    vec2<f32>(xy.x, 1.0 - xy.y),
+  );
+}
+
+// The snapshot is mixed over the frame by the blend constant. It may be a
+// different size than the target after a resize, so it's stretched to fit.
+@fragment
+fn main_fs(fs_input: VertexOutput) -> @location(0) vec4<f32> {
+  let color = textureSample(snapshot_texture, snapshot_sampler, fs_input.uv).rgb;
+  return vec4<f32>(color, 1.0);
+}
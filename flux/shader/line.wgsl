@@ -12,6 +12,13 @@ struct LineUniforms {
   color_mode: u32,
   delta_time: f32,
   brightness_scale: f32,
+  max_saturation: f32,
+  max_lightness: f32,
+  base_intensity: f32,
+  linear_output: u32,
+  line_curvature: f32,
+  line_taper: f32,
+  color_mapping: u32,
 }
 
 @group(0) @binding(0) var<uniform> uniforms: LineUniforms;
@@ -24,6 +31,17 @@ struct VertexOutput {
   @location(2) f_line_offset: f32,
 }
 
+// How far back along its path the tip is traced, in seconds
+const BEND_TIME: f32 = 0.05;
+
+// The offset of the middle of the line from a straight line. Capped to half
+// the length of the line, so that fast lines don't fold over themselves.
+fn get_bend(endpoint: vec2<f32>, velocity: vec2<f32>) -> vec2<f32> {
+  let bend = -uniforms.line_curvature * BEND_TIME * velocity;
+  let max_length = 0.5 * length(endpoint);
+  return bend * min(1.0, max_length / max(length(bend), 1e-10));
+}
+
 @vertex
 fn main_vs(
   @location(0) endpoint: vec2<f32>, // 0
@@ -34,12 +52,22 @@ fn main_vs(
   @location(5) basepoint: vec2<f32>, // 48
   @location(6) vertex: vec2<f32>, // 56
 ) -> VertexOutput { // 64
-  var x_basis = vec2<f32>(-endpoint.y, endpoint.x);
+  // Bend the line into a parabola that trails behind the moving tip, like a
+  // brush dragged through the flow. Single-segment lines get no curvature, so
+  // they're drawn as a straight quad.
+  let bend = get_bend(endpoint, velocity);
+  let along = 4.0 * vertex.y * (1.0 - vertex.y);
+  let tangent = endpoint + 4.0 * (1.0 - 2.0 * vertex.y) * bend;
+
+  var x_basis = vec2<f32>(-tangent.y, tangent.x);
   x_basis /= max(length(x_basis), 1e-10); // safely normalize
 
+  let taper = mix(1.0, vertex.y, uniforms.line_taper);
+
   var point = vec2<f32>(uniforms.aspect, 1.0) * uniforms.zoom * (basepoint * 2.0 - 1.0)
     + endpoint * vertex.y
-    + uniforms.line_width * width * x_basis * vertex.x;
+    + bend * along
+    + uniforms.line_width * width * taper * x_basis * vertex.x;
 
   point.x /= uniforms.aspect;
 
@@ -114,13 +142,18 @@ fn hsl_to_rgb(hsl: vec3<f32>) -> vec3<f32> {
   return rgb + m;
 }
 
+// Decode sRGB-encoded RGB to linear
+fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
+  let low = srgb / 12.92;
+  let high = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+  return select(high, low, srgb <= vec3<f32>(0.04045));
+}
+
 // Cap saturation and luminance to reduce eye strain
 fn cap_brightness(rgb: vec3<f32>) -> vec3<f32> {
   var hsl = rgb_to_hsl(rgb);
-  // Cap saturation at 25% for very muted colors
-  hsl.y = min(hsl.y, 0.25);
-  // Cap luminance at 30% - much darker
-  hsl.z = min(hsl.z, 0.30);
+  hsl.y = min(hsl.y, uniforms.max_saturation);
+  hsl.z = min(hsl.z, uniforms.max_lightness);
   return hsl_to_rgb(hsl);
 }
 
@@ -134,7 +167,20 @@ fn main_fs(fs_input: VertexOutput) -> @location(0) vec4<f32> {
   // Apply brightness capping to reduce eye strain
   let capped_color = cap_brightness(fs_input.f_color.rgb);
   // Scale color by brightness_scale (based on line count) to normalize across displays
-  // Base multiplier 0.3 for darker overall look, then scale by line count
-  let scaled_color = capped_color * uniforms.brightness_scale * 0.3;
-  return vec4<f32>(scaled_color, fs_input.f_color.a * fade * smooth_edges);
+  // Base intensity for a darker overall look, then scale by line count
+  let scaled_color = capped_color * uniforms.brightness_scale * uniforms.base_intensity;
+  // Premultiply, so that every blend mode can be expressed with fixed factors
+  let alpha = fs_input.f_color.a * fade * smooth_edges;
+  return vec4<f32>(encode_output(scaled_color * alpha), alpha);
+}
+
+// Line colors stay sRGB-encoded through every step above. Decode the final
+// premultiplied color, once, for targets that expect linear colors. Over
+// black, an encoded target then stores c * a and a linear target stores
+// decode(c * a), which display the same.
+fn encode_output(premultiplied: vec3<f32>) -> vec3<f32> {
+  if (uniforms.linear_output == 1u) {
+    return srgb_to_linear(premultiplied);
+  }
+  return premultiplied;
 }
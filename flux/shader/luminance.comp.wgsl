@@ -0,0 +1,133 @@
+struct ExposureUniforms {
+  target_luminance: f32,
+  highlight_percentile: f32,
+  adaptation_rate: f32,
+  min_exposure: f32,
+  max_exposure: f32,
+  delta_time: f32,
+  auto_exposure: u32,
+  exposure: f32,
+  tone_mapping: u32,
+  headroom: f32,
+}
+
+struct Histogram {
+  bins: array<atomic<u32>, 64>,
+}
+
+struct Exposure {
+  exposure: f32,
+  average_luminance: f32,
+  highlight_luminance: f32,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: ExposureUniforms;
+@group(0) @binding(1) var<storage, read_write> histogram: Histogram;
+@group(0) @binding(2) var<storage, read_write> exposure: Exposure;
+@group(0) @binding(3) var scene_texture: texture_2d<f32>;
+
+// The scene is metered on a fixed grid of samples, regardless of its size.
+const SAMPLE_GRID = 256u;
+const BIN_COUNT = 64u;
+
+// Bin 0 collects black pixels. The remaining bins cover luminance on a log2
+// scale from MIN_LOG_LUMINANCE to MIN_LOG_LUMINANCE + LOG_LUMINANCE_RANGE.
+const MIN_LOG_LUMINANCE = -12.0;
+const LOG_LUMINANCE_RANGE = 16.0;
+
+var<workgroup> local_bins: array<atomic<u32>, 64>;
+
+fn get_luminance(rgb: vec3<f32>) -> f32 {
+  return dot(rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+fn get_bin(luminance: f32) -> u32 {
+  if (luminance < exp2(MIN_LOG_LUMINANCE)) {
+    return 0u;
+  }
+
+  let t = (log2(luminance) - MIN_LOG_LUMINANCE) / LOG_LUMINANCE_RANGE;
+  return 1u + u32(clamp(t * f32(BIN_COUNT - 1u), 0.0, f32(BIN_COUNT - 2u)));
+}
+
+fn get_bin_luminance(bin: u32) -> f32 {
+  if (bin == 0u) {
+    return 0.0;
+  }
+
+  let t = (f32(bin - 1u) + 0.5) / f32(BIN_COUNT - 1u);
+  return exp2(MIN_LOG_LUMINANCE + t * LOG_LUMINANCE_RANGE);
+}
+
+@compute
+@workgroup_size(16, 16, 1)
+fn measure(
+  @builtin(global_invocation_id) global_id: vec3<u32>,
+  @builtin(local_invocation_index) local_index: u32,
+) {
+  if (local_index < BIN_COUNT) {
+    atomicStore(&local_bins[local_index], 0u);
+  }
+  workgroupBarrier();
+
+  let size = textureDimensions(scene_texture);
+  let position = (vec2<f32>(global_id.xy) + 0.5) / f32(SAMPLE_GRID);
+  let texel = min(vec2<u32>(position * vec2<f32>(size)), size - 1u);
+  let luminance = get_luminance(textureLoad(scene_texture, texel, 0).rgb);
+
+  atomicAdd(&local_bins[get_bin(luminance)], 1u);
+  workgroupBarrier();
+
+  if (local_index < BIN_COUNT) {
+    atomicAdd(&histogram.bins[local_index], atomicLoad(&local_bins[local_index]));
+  }
+}
+
+@compute
+@workgroup_size(1, 1, 1)
+fn adapt() {
+  let sample_count = f32(SAMPLE_GRID * SAMPLE_GRID);
+
+  // Walk the histogram to find the average luminance and that of the
+  // brightest pixels, clearing it for the next frame as we go. The bins are
+  // spaced on a log scale, so dim scenes are metered as precisely as bright
+  // ones.
+  let highlight_count = uniforms.highlight_percentile * sample_count;
+  var cumulative_count = 0.0;
+  var luminance_sum = 0.0;
+  var highlight_luminance = 0.0;
+  var found_highlight = false;
+  for (var bin = 0u; bin < BIN_COUNT; bin++) {
+    let count = f32(atomicLoad(&histogram.bins[bin]));
+    atomicStore(&histogram.bins[bin], 0u);
+    cumulative_count += count;
+    luminance_sum += count * get_bin_luminance(bin);
+
+    if (!found_highlight && cumulative_count >= highlight_count) {
+      highlight_luminance = get_bin_luminance(bin);
+      found_highlight = true;
+    }
+  }
+  let average_luminance = luminance_sum / sample_count;
+
+  exposure.average_luminance = average_luminance;
+  exposure.highlight_luminance = highlight_luminance;
+
+  // Nothing has been drawn yet. Hold the current exposure.
+  if (average_luminance <= 0.0) {
+    return;
+  }
+
+  // Expose for the average, but don't let the highlights clip.
+  let average_exposure = uniforms.target_luminance / average_luminance;
+  let highlight_exposure = 1.0 / max(highlight_luminance, 1e-4);
+  let target_exposure = clamp(
+    min(average_exposure, highlight_exposure),
+    uniforms.min_exposure,
+    uniforms.max_exposure,
+  );
+
+  // Adapt smoothly in log space, independently of the frame rate
+  let blend = 1.0 - exp(-uniforms.adaptation_rate * uniforms.delta_time);
+  exposure.exposure = exp2(mix(log2(exposure.exposure), log2(target_exposure), blend));
+}
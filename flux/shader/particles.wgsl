@@ -0,0 +1,145 @@
+struct Particle {
+  position: vec2<f32>,
+  velocity: vec2<f32>,
+  age: f32,
+  lifetime: f32,
+}
+
+struct ParticleUniforms {
+  view_matrix: mat4x4<f32>,
+  palette: array<vec4<f32>, 6>,
+  palette_size: u32,
+  linear_output: u32,
+  frame: u32,
+  delta_time: f32,
+  flow_speed: f32,
+  lifetime: f32,
+  size: vec2<f32>,
+  brightness: f32,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: ParticleUniforms;
+@group(0) @binding(1) var linear_sampler: sampler;
+
+@group(1) @binding(0) var<storage, read_write> particles: array<Particle>;
+
+@group(2) @binding(0) var velocity_texture: texture_2d<f32>;
+
+const pi = 3.141592653589793;
+const tau = 2.0 * pi;
+
+@compute
+@workgroup_size(64)
+fn update(
+  @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+  let index = global_id.x;
+  if (index >= arrayLength(&particles)) {
+    return;
+  }
+
+  var particle = particles[index];
+  particle.age += uniforms.delta_time;
+
+  // Respawn somewhere new once the particle dies or leaves the screen
+  let outside = any(particle.position < vec2<f32>(0.0)) || any(particle.position > vec2<f32>(1.0));
+  if (particle.age >= particle.lifetime || outside) {
+    let seed = hash(index ^ hash(uniforms.frame));
+    particle.position = vec2<f32>(random(seed), random(seed + 1u));
+    particle.age = 0.0;
+    particle.lifetime = uniforms.lifetime * mix(0.5, 1.5, random(seed + 2u));
+  }
+
+  let velocity = textureSampleLevel(velocity_texture, linear_sampler, particle.position, 0.0).xy;
+  particle.velocity = velocity;
+  particle.position += uniforms.delta_time * uniforms.flow_speed * velocity;
+
+  particles[index] = particle;
+}
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) corner: vec2<f32>,
+  @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn main_vs(
+  @builtin(vertex_index) vertex_index: u32,
+  @location(0) position: vec2<f32>,
+  @location(1) velocity: vec2<f32>,
+  @location(2) age: f32,
+  @location(3) lifetime: f32,
+) -> VertexOutput {
+  var corners = array<vec2<f32>, 6>(
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, 1.0),
+  );
+  let corner = corners[vertex_index];
+
+  let center = uniforms.view_matrix * vec4<f32>(2.0 * position - 1.0, 0.0, 1.0);
+  let offset = vec4<f32>(corner * uniforms.size, 0.0, 0.0) * center.w;
+
+  // Fade in after spawning and out before respawning
+  let progress = age / max(lifetime, 1e-3);
+  let fade = smoothstep(0.0, 0.1, progress) * (1.0 - smoothstep(0.7, 1.0, progress));
+  // Particles in still parts of the fluid are dimmer
+  let speed = mix(0.25, 1.0, saturate(2.5 * length(velocity)));
+
+  var color = get_color(velocity);
+  if (uniforms.linear_output == 1u) {
+    color = srgb_to_linear(color);
+  }
+
+  var out: VertexOutput;
+  out.position = center + offset;
+  out.corner = corner;
+  out.color = vec4<f32>(color * uniforms.brightness, fade * speed);
+  return out;
+}
+
+@fragment
+fn main_fs(in: VertexOutput) -> @location(0) vec4<f32> {
+  let shape = 1.0 - smoothstep(0.5, 1.0, length(in.corner));
+  let alpha = in.color.a * shape;
+  return vec4<f32>(in.color.rgb * alpha, alpha);
+}
+
+// Pick a color from the palette by the direction of the flow, or derive one
+// from the velocity without a palette.
+fn get_color(velocity: vec2<f32>) -> vec3<f32> {
+  let size = uniforms.palette_size;
+  if (size == 0u) {
+    return vec3<f32>(saturate(vec2<f32>(1.0, 0.66) * (0.5 + velocity)), 0.5);
+  }
+
+  let raw_index = (atan2(velocity.y, velocity.x) + pi) / tau * f32(size);
+  let index = u32(floor(raw_index)) % size;
+  let next_index = (index + 1u) % size;
+  return mix(uniforms.palette[index], uniforms.palette[next_index], fract(raw_index)).rgb;
+}
+
+fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
+  let low = srgb / 12.92;
+  let high = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+  return select(high, low, srgb <= vec3<f32>(0.04045));
+}
+
+fn hash(value: u32) -> u32 {
+  var x = value;
+  x ^= x >> 16u;
+  x *= 0x7feb352du;
+  x ^= x >> 15u;
+  x *= 0x846ca68bu;
+  x ^= x >> 16u;
+  return x;
+}
+
+// A random number in [0, 1)
+fn random(seed: u32) -> f32 {
+  return f32(hash(seed) >> 8u) / 16777216.0;
+}
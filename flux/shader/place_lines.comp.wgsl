@@ -21,12 +21,20 @@ struct LineUniforms {
   color_mode: u32,
   delta_time: f32,
   brightness_scale: f32,
+  max_saturation: f32,
+  max_lightness: f32,
+  base_intensity: f32,
+  linear_output: u32,
+  line_curvature: f32,
+  line_taper: f32,
+  color_mapping: u32,
 }
 
 @group(0) @binding(0) var<uniform> uniforms: LineUniforms;
 @group(0) @binding(1) var<storage, read> basepoints: array<vec2<f32>>;
 @group(0) @binding(2) var linear_sampler: sampler;
 @group(0) @binding(3) var color_texture_sampler: sampler;
+@group(0) @binding(4) var<storage, read> line_weights: array<f32>;
 
 @group(1) @binding(0) var<storage, read> lines: array<Line>;
 @group(1) @binding(1) var<storage, read_write> out_lines: array<Line>;
@@ -148,8 +156,11 @@ fn main(
   // Basically, smoothstep(0.0, 0.4, length(velocity));
   // Maybe width and opacity should be on different easings.
   let width_boost = saturate(2.5 * length(velocity));
-  let new_line_width = smoothstep(0.0, 1.0, width_boost);
-  let opacity = smoothstep(0.0, 1.0, width_boost);
+  // Lines in sparse parts of the density map are wider and brighter, and
+  // lines in dense parts are thinner and dimmer.
+  let weight = line_weights[index];
+  let new_line_width = weight * smoothstep(0.0, 1.0, width_boost);
+  let opacity = saturate(weight * smoothstep(0.0, 1.0, width_boost));
 
   var color: vec3<f32>;
   var color_momentum_boost = 3.0;
@@ -174,6 +185,29 @@ fn main(
       color_momentum_boost = 5.0;
       color_delta_boost = 10.0;
     }
+
+    // sRGB texture. The sampler decodes the texels and filters them in linear
+    // space, which keeps resampled photos from looking washed out. That's the
+    // only step in linear space: re-encode straight away, so that the caps and
+    // the easing below see the same sRGB colors as every other mode. The
+    // fragment shaders decode once more for linear targets.
+    case 3u: {
+      let sampled = textureSampleLevel(color_texture, color_texture_sampler, 2.0 * velocity + 0.5, 0.0).rgb;
+      color = linear_to_srgb(saturate(sampled));
+      color_momentum_boost = 5.0;
+      color_delta_boost = 10.0;
+    }
+  }
+
+  // Recolor by the local flow instead
+  switch uniforms.color_mapping {
+    case 1u: {
+      color = get_angle_color(velocity);
+    }
+    case 2u: {
+      color = get_speed_color(velocity);
+    }
+    default: {}
   }
 
   // Cap the target color at source before interpolation
@@ -204,6 +238,62 @@ fn main(
 const pi = 3.141592653589793;
 const tau = 2.0 * pi;
 
+// Lines reach their full width at about this speed, so it's colored as fast
+const MAX_COLOR_SPEED = 0.4;
+
+// Go around the palette with the direction of the flow
+fn get_angle_color(velocity: vec2<f32>) -> vec3<f32> {
+  let angle = atan2(velocity.y, velocity.x);
+  var color: vec3<f32>;
+
+  switch uniforms.color_mode {
+    case 1u: {
+      color = get_color(angle + pi, tau).rgb;
+    }
+    // A ring around the center of the image
+    case 2u, 3u: {
+      color = sample_color_texture(0.5 + 0.4 * vec2<f32>(cos(angle), sin(angle)));
+    }
+    case 0u, default: {
+      color = hsl_to_rgb(vec3<f32>((angle + pi) / tau, 1.0, 0.5));
+    }
+  }
+
+  return color;
+}
+
+// Ramp from the first color of the palette in calm parts of the flow to the
+// last color in fast parts
+fn get_speed_color(velocity: vec2<f32>) -> vec3<f32> {
+  let speed = saturate(length(velocity) / MAX_COLOR_SPEED);
+  var color: vec3<f32>;
+
+  switch uniforms.color_mode {
+    case 1u: {
+      let size = f32(arrayLength(&color_buffer));
+      color = get_color(speed * (size - 1.0), size).rgb;
+    }
+    // Across the middle of the image
+    case 2u, 3u: {
+      color = sample_color_texture(vec2<f32>(speed, 0.5));
+    }
+    // From blue to red
+    case 0u, default: {
+      color = hsl_to_rgb(vec3<f32>(mix(0.66, 0.0, speed), 1.0, 0.5));
+    }
+  }
+
+  return color;
+}
+
+fn sample_color_texture(uv: vec2<f32>) -> vec3<f32> {
+  let sampled = textureSampleLevel(color_texture, color_texture_sampler, uv, 0.0).rgb;
+  if (uniforms.color_mode == 3u) {
+    return linear_to_srgb(saturate(sampled));
+  }
+  return sampled;
+}
+
 // Convert RGB to HSL
 fn rgb_to_hsl(rgb: vec3<f32>) -> vec3<f32> {
   let max_c = max(max(rgb.r, rgb.g), rgb.b);
@@ -262,13 +352,18 @@ fn hsl_to_rgb(hsl: vec3<f32>) -> vec3<f32> {
   return rgb + m;
 }
 
+// Encode linear RGB with the sRGB transfer function
+fn linear_to_srgb(rgb: vec3<f32>) -> vec3<f32> {
+  let low = rgb * 12.92;
+  let high = 1.055 * pow(rgb, vec3<f32>(1.0 / 2.4)) - 0.055;
+  return select(high, low, rgb <= vec3<f32>(0.0031308));
+}
+
 // Cap saturation and luminance at source to prevent bright accumulation
 fn cap_brightness(rgb: vec3<f32>) -> vec3<f32> {
   var hsl = rgb_to_hsl(rgb);
-  // Cap saturation at 25% for very muted colors
-  hsl.y = min(hsl.y, 0.25);
-  // Cap luminance at 30% - much darker
-  hsl.z = min(hsl.z, 0.30);
+  hsl.y = min(hsl.y, uniforms.max_saturation);
+  hsl.z = min(hsl.z, uniforms.max_lightness);
   return hsl_to_rgb(hsl);
 }
 
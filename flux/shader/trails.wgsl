@@ -0,0 +1,21 @@
+@group(0) @binding(0) var accumulation_texture: texture_2d<f32>;
+
+// Draw a single triangle that covers the screen
+@vertex
+fn main_vs(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+  let xy = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+  return vec4<f32>(2.0 * xy - 1.0, 0.0, 1.0);
+}
+
+// The previous frame is scaled by the blend constant. There's nothing to add.
+@fragment
+fn fade() -> @location(0) vec4<f32> {
+  return vec4<f32>(0.0);
+}
+
+// The accumulation texture is the same size as the target
+@fragment
+fn blit(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+  let color = textureLoad(accumulation_texture, vec2<i32>(position.xy), 0).rgb;
+  return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,110 @@
+// Generate a tileable blue noise texture with the void-and-cluster method.
+//
+// Pixels are ranked by repeatedly picking the one furthest from everything
+// picked so far, measured with a toroidal Gaussian energy. Neighbouring ranks
+// end up far apart, so thresholding the texture at any level gives an even,
+// clump-free pattern. This makes it ideal for dithering.
+//
+// The output is deterministic. It doesn't touch the shared RNG, so enabling
+// dithering doesn't change the simulation for a given seed.
+
+const SIGMA: f32 = 1.5;
+
+/// Generate a `size` by `size` texture of ranks, scaled to the full range of a
+/// byte. `size` must be a power of two.
+pub fn generate(size: u32) -> Vec<u8> {
+    assert!(
+        size.is_power_of_two(),
+        "blue noise size must be a power of two"
+    );
+
+    let size = size as usize;
+    let mask = size - 1;
+    let count = size * size;
+
+    // The energy contributed by a point at each wrapped offset
+    let kernel: Vec<f32> = (0..count)
+        .map(|i| {
+            let dx = wrapped_distance(i % size, size) as f32;
+            let dy = wrapped_distance(i / size, size) as f32;
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+
+    // Break ties with a tiny per-pixel offset. Otherwise, the first points
+    // land on a regular lattice.
+    let mut energy: Vec<f32> = (0..count).map(|i| 1e-4 * hash(i as u32)).collect();
+    let mut ranks = vec![None; count];
+
+    for rank in 0..count {
+        let void = (0..count)
+            .filter(|&i| ranks[i].is_none())
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap();
+        ranks[void] = Some(rank);
+
+        let (px, py) = (void % size, void / size);
+        for y in 0..size {
+            let row = ((y.wrapping_sub(py)) & mask) * size;
+            for x in 0..size {
+                energy[y * size + x] += kernel[row + ((x.wrapping_sub(px)) & mask)];
+            }
+        }
+    }
+
+    ranks
+        .into_iter()
+        .map(|rank| (rank.unwrap() * 256 / count) as u8)
+        .collect()
+}
+
+fn wrapped_distance(offset: usize, size: usize) -> usize {
+    offset.min(size - offset)
+}
+
+// A small integer hash, mapped to [0, 1)
+fn hash(mut x: u32) -> f32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846ca68b);
+    x ^= x >> 16;
+    (x >> 8) as f32 / (1 << 24) as f32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn uses_every_level_equally() {
+        let noise = generate(32);
+        let mut histogram = [0; 256];
+        noise
+            .iter()
+            .for_each(|&value| histogram[value as usize] += 1);
+        assert!(histogram.iter().all(|&count| count == 32 * 32 / 256));
+    }
+
+    #[test]
+    fn spreads_out_low_ranks() {
+        let size = 32;
+        let noise = generate(size);
+
+        // No two of the darkest pixels should touch
+        let darkest: Vec<(usize, usize)> = noise
+            .iter()
+            .enumerate()
+            .filter(|(_, &value)| value < 16)
+            .map(|(i, _)| (i % size as usize, i / size as usize))
+            .collect();
+
+        for (i, a) in darkest.iter().enumerate() {
+            for b in &darkest[i + 1..] {
+                let dx = wrapped_distance(a.0.abs_diff(b.0), size as usize);
+                let dy = wrapped_distance(a.1.abs_diff(b.1), size as usize);
+                assert!(dx > 1 || dy > 1, "{:?} and {:?} are adjacent", a, b);
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+// A slow "Ken Burns" camera that drifts across the lines.
+//
+// The camera zooms in, which leaves room around the view, and pans within that
+// room. Both follow a smooth path made of a few slow waves with incommensurate
+// frequencies, so the path never visibly repeats. The phases are seeded, so
+// the same seed takes the same path.
+
+use crate::rng;
+use crate::settings::CameraDrift;
+
+use rand::Rng;
+use std::f64::consts::TAU;
+
+// The waves that make up the path, in radians per second at a speed of 1.0.
+// The ratios are irrational, so the waves never line up again.
+const FREQUENCIES: [f64; 3] = [0.031, 0.031 * 0.618_034, 0.031 * 0.414_214];
+const AMPLITUDES: [f64; 3] = [0.5, 0.3, 0.2];
+
+// Even at its widest, the camera stays zoomed in this far into its range, so
+// that it always has some room to pan.
+const MIN_ZOOM: f32 = 0.25;
+
+pub struct Camera {
+    // In seconds at a speed of 1.0. Kept as a double, so the path stays smooth
+    // however long the wallpaper runs.
+    time: f64,
+    // One set of phases for each of x, y and zoom
+    phases: [[f64; 3]; 3],
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        let mut rng = rng::seeded("camera");
+        let mut phases = [[0.0; 3]; 3];
+        for phase in phases.iter_mut().flatten() {
+            *phase = TAU * rng.random::<f64>();
+        }
+
+        Self { time: 0.0, phases }
+    }
+
+    pub fn tick(&mut self, timestep: f32, drift: &CameraDrift) {
+        self.time += (drift.speed.max(0.0) * timestep) as f64;
+    }
+
+    /// The zoom, and the point on the canvas to center the view on.
+    pub fn get_view(&self, drift: &CameraDrift) -> (f32, [f32; 2]) {
+        let range = drift.range.max(0.0);
+        let zoom = 1.0 + range * (MIN_ZOOM + (1.0 - MIN_ZOOM) * (0.5 + 0.5 * self.wave(2)));
+
+        // Pan as far as the zoom allows without showing past the edge
+        let room = 1.0 - 1.0 / zoom;
+        (zoom, [room * self.wave(0), room * self.wave(1)])
+    }
+
+    // A smooth wave in [-1, 1]
+    fn wave(&self, axis: usize) -> f32 {
+        FREQUENCIES
+            .iter()
+            .zip(AMPLITUDES)
+            .zip(self.phases[axis])
+            .map(|((frequency, amplitude), phase)| {
+                amplitude * (frequency * self.time + phase).sin()
+            })
+            .sum::<f64>() as f32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stays_within_canvas() {
+        let drift = CameraDrift::default();
+        let mut camera = Camera::new();
+        for _ in 0..10_000 {
+            camera.tick(1.0, &drift);
+            let (zoom, pan) = camera.get_view(&drift);
+            assert!(zoom >= 1.0 && zoom <= 1.0 + drift.range);
+            // The edges of the view stay on the canvas
+            for offset in pan {
+                assert!(offset.abs() + 1.0 / zoom <= 1.0 + 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn moves_smoothly() {
+        let drift = CameraDrift::default();
+        let mut camera = Camera::new();
+        let (mut last_zoom, mut last_pan) = camera.get_view(&drift);
+        for _ in 0..1_000 {
+            camera.tick(1.0 / 60.0, &drift);
+            let (zoom, pan) = camera.get_view(&drift);
+            assert!((zoom - last_zoom).abs() < 1e-3);
+            assert!((pan[0] - last_pan[0]).abs() < 1e-3);
+            assert!((pan[1] - last_pan[1]).abs() < 1e-3);
+            (last_zoom, last_pan) = (zoom, pan);
+        }
+    }
+}
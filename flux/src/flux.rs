@@ -1,4 +1,4 @@
-use crate::{grid, render, rng, settings};
+use crate::{camera, grid, layer, render, rng, settings, tween};
 use settings::Settings;
 
 use std::sync::Arc;
@@ -7,19 +7,40 @@ use std::sync::Mutex;
 // The time at which the animation timer will reset to zero.
 const MAX_ELAPSED_TIME: f32 = 1000.0;
 const MAX_FRAME_TIME: f32 = 1.0 / 10.0;
+const MIN_RENDER_SCALE: f32 = 0.25;
 
 pub struct Flux {
     settings: Arc<Settings>,
+    swapchain_format: wgpu::TextureFormat,
     logical_size: wgpu::Extent3d,
     physical_size: wgpu::Extent3d,
+    // The size of the offscreen targets
+    scene_size: wgpu::Extent3d,
 
     grid: grid::Grid,
     fluid: render::fluid::Context,
     pub lines: render::lines::Context,
+    // Drawn behind the main lines, from back to front
+    layers: Vec<layer::Layer>,
+    // The colors last given to the main lines. Layers without their own
+    // palette share them.
+    shared_color_texture_view: Option<wgpu::TextureView>,
+    shared_color_buffer: Option<wgpu::Buffer>,
     noise_generator: render::noise::NoiseGenerator,
     debug_texture: render::texture::Context,
+    exposure: Option<render::exposure::Context>,
+    composite: Option<render::composite::Context>,
+    background: render::background::Context,
+    bloom: Option<render::bloom::Context>,
+    trails: Option<render::trails::Context>,
+    fade: render::fade::Context,
+    // Alternatives to the lines, depending on the mode
+    particles: Option<render::particles::Context>,
+    dye: Option<render::dye::Context>,
+    msaa: Option<render::msaa::Context>,
 
     pub color_image: Arc<Mutex<Option<image::RgbaImage>>>,
+    color_animation: Option<render::color::AnimatedTexture>,
 
     // A timestamp in milliseconds. Either host or video time.
     last_timestamp: f64,
@@ -28,6 +49,16 @@ pub struct Flux {
     elapsed_time: f32,
 
     fluid_frame_time: f32,
+
+    // How far the view has turned on its own, in degrees
+    view_angle: f32,
+    camera: camera::Camera,
+    safe_zones: render::SafeZones,
+    // Eases numeric settings to their new values
+    tween: Option<tween::Tween>,
+    // The lines and layers to lay out again after the next frame
+    lines_reset_pending: bool,
+    layers_reset_pending: bool,
 }
 
 impl Flux {
@@ -36,13 +67,407 @@ impl Flux {
         self.settings.grid_spacing
     }
 
+    /// Apply new settings. Numeric settings are eased to their new values
+    /// over `Settings::tween_duration`.
     pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, settings: &Arc<Settings>) {
+        // A preset replaces any colors sampled for the old settings. Callers
+        // sample them again if they still want them.
+        if let settings::ColorMode::Preset(_) = settings.color_mode {
+            self.shared_color_texture_view = None;
+            self.shared_color_buffer = None;
+        }
+
+        if settings.tween_duration > 0.0 {
+            // Start from wherever a tween in progress has got to
+            let tween = tween::Tween::new(&self.settings, settings);
+            self.apply_settings(device, queue, &Arc::new(tween.get_setup_settings()));
+            self.apply_eased_settings(device, queue, &Arc::new(tween.get_settings()));
+            self.tween = Some(tween);
+        } else {
+            self.tween = None;
+            self.apply_settings(device, queue, settings);
+        }
+    }
+
+    fn apply_settings(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        settings: &Arc<Settings>,
+    ) {
+        let grid_layout_changed = settings.grid_layout != self.settings.grid_layout
+            || settings.line_density != self.settings.line_density;
+        let layers_changed = grid_layout_changed || settings.layers != self.settings.layers;
+
+        self.fade.update(settings);
         self.settings = Arc::clone(settings);
+
+        // Lay out the lines again. Like a resize, this resets their state.
+        if layers_changed {
+            self.reset_lines(device, queue, grid_layout_changed);
+        }
+
+        if let settings::ColorMode::Preset(_) = self.settings.color_mode {
+            self.color_animation = None;
+        }
         self.fluid
             .update(device, queue, self.grid.scaling_ratio, &self.settings);
         self.noise_generator.update(&self.settings);
+
+        self.background.update(device, queue, &self.settings);
+
+        // The offscreen scene can be larger or smaller than the screen
+        let scene_size = get_scene_size(device, self.physical_size, &self.settings);
+        if scene_size != self.scene_size {
+            self.scene_size = scene_size;
+            self.resize_scene(device);
+        }
+
+        // Draw the lines offscreen for HDR, wide gamut, auto-exposure and the
+        // screen effects
+        match (
+            uses_scene_target(&self.settings),
+            &mut self.exposure,
+            &mut self.composite,
+        ) {
+            (true, Some(exposure), Some(composite)) => {
+                exposure.update(&self.settings);
+                composite.update(&self.settings);
+            }
+            (true, _, _) => {
+                let exposure = render::exposure::Context::new(
+                    device,
+                    self.swapchain_format,
+                    self.scene_size,
+                    &self.settings,
+                );
+                self.composite = Some(self.create_composite(device, queue, &exposure));
+                self.exposure = Some(exposure);
+                self.set_lines_target_format(device, queue, render::exposure::SCENE_FORMAT);
+            }
+            (false, None, None) => {}
+            (false, _, _) => {
+                self.exposure = None;
+                self.composite = None;
+                self.set_lines_target_format(device, queue, self.swapchain_format);
+            }
+        }
+
+        // The glow is drawn on top of the offscreen scene
+        match (
+            &self.exposure,
+            self.settings.bloom_intensity > 0.0,
+            &mut self.bloom,
+        ) {
+            (Some(_), true, Some(bloom)) => bloom.update(queue, &self.settings),
+            (Some(exposure), true, None) => {
+                self.bloom = Some(render::bloom::Context::new(
+                    device,
+                    self.scene_size,
+                    exposure.get_scene_texture_view(),
+                    &self.settings,
+                ));
+            }
+            _ => self.bloom = None,
+        }
+
+        let sample_count = render::msaa::get_sample_count(&self.settings);
+        match (render::trails::is_enabled(&self.settings), &mut self.trails) {
+            (true, Some(trails)) if trails.get_sample_count() == sample_count => {
+                trails.update(&self.settings)
+            }
+            (true, _) => {
+                self.trails = Some(render::trails::Context::new(
+                    device,
+                    self.scene_size,
+                    &self.settings,
+                ));
+            }
+            (false, _) => self.trails = None,
+        }
+
+        match (&self.exposure, sample_count > 1, &self.msaa) {
+            (Some(_), true, Some(_)) => {}
+            (Some(_), true, None) => {
+                self.msaa = Some(render::msaa::Context::new(device, self.scene_size));
+            }
+            _ => self.msaa = None,
+        }
+
+        match (&self.settings.mode, &mut self.particles) {
+            (settings::Mode::Particles, Some(particles)) => {
+                particles.update(device, queue, self.logical_size, &self.settings)
+            }
+            (settings::Mode::Particles, None) => {
+                self.particles = Some(render::particles::Context::new(
+                    device,
+                    self.get_lines_format(),
+                    self.logical_size,
+                    &self.settings,
+                ));
+            }
+            _ => self.particles = None,
+        }
+
+        let fluid_size = self.fluid.get_fluid_size();
+        match (&self.settings.mode, &mut self.dye) {
+            (settings::Mode::Dye, Some(dye)) if dye.get_fluid_size() == fluid_size => {
+                dye.update(device, queue, &self.settings)
+            }
+            (settings::Mode::Dye, _) => {
+                self.dye = Some(render::dye::Context::new(
+                    device,
+                    self.get_lines_format(),
+                    fluid_size,
+                    &self.settings,
+                ));
+            }
+            _ => self.dye = None,
+        }
+
         self.lines
             .update(device, queue, self.logical_size, &self.grid, &self.settings);
+
+        // Layers about to be laid out again keep their old look until then
+        if !self.layers_reset_pending {
+            for layer in &mut self.layers {
+                layer.update(device, queue, self.logical_size, &self.settings);
+            }
+        }
+    }
+
+    /// Lay out the lines again, and the main lines too if `reset_main_lines`.
+    /// With fades enabled, this waits until the next frame has been drawn, to
+    /// crossfade from it.
+    fn reset_lines(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, reset_main_lines: bool) {
+        self.lines_reset_pending |= reset_main_lines;
+        self.layers_reset_pending = true;
+
+        if self.fade.is_enabled() {
+            self.fade.request_snapshot();
+        } else {
+            self.lay_out_lines(device, queue);
+        }
+    }
+
+    fn lay_out_lines(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.lines_reset_pending {
+            self.grid = grid::Grid::new(
+                self.logical_size.width,
+                self.logical_size.height,
+                &self.settings,
+            );
+            self.lines
+                .resize(device, queue, self.logical_size, &self.grid, &self.settings);
+
+            // self.fluid.resize(device, self.grid.scaling_ratio);
+            self.noise_generator.resize(
+                device,
+                2 * self.settings.fluid_size,
+                self.grid.scaling_ratio,
+            );
+        }
+        if self.layers_reset_pending {
+            self.layers = self.create_layers(device, queue);
+        }
+        self.lines_reset_pending = false;
+        self.layers_reset_pending = false;
+
+        // Resetting the lines also resets their colors
+        self.bind_shared_colors(device, queue);
+    }
+
+    /// Apply settings part way through a tween. Only the uniforms change, as
+    /// `apply_settings` set up everything else when the tween started.
+    fn apply_eased_settings(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        settings: &Arc<Settings>,
+    ) {
+        self.settings = Arc::clone(settings);
+
+        self.fluid
+            .update(device, queue, self.grid.scaling_ratio, &self.settings);
+        self.noise_generator.update(&self.settings);
+        self.background.update(device, queue, &self.settings);
+
+        if let Some(exposure) = &mut self.exposure {
+            exposure.update(&self.settings);
+        }
+        if let Some(composite) = &mut self.composite {
+            composite.update(&self.settings);
+        }
+        if let Some(bloom) = &mut self.bloom {
+            bloom.update(queue, &self.settings);
+        }
+        if let Some(trails) = &mut self.trails {
+            trails.update(&self.settings);
+        }
+        if let Some(particles) = &mut self.particles {
+            particles.update(device, queue, self.logical_size, &self.settings);
+        }
+        if let Some(dye) = &mut self.dye {
+            dye.update(device, queue, &self.settings);
+        }
+
+        self.lines
+            .update_uniforms(queue, self.logical_size, &self.grid, &self.settings);
+        for layer in &mut self.layers {
+            layer.update_uniforms(queue, self.logical_size, &self.settings);
+        }
+    }
+
+    fn create_layers(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<layer::Layer> {
+        let mut layers = layer::create_layers(
+            device,
+            queue,
+            self.get_lines_format(),
+            self.logical_size,
+            &self.settings,
+        );
+
+        if self.shared_color_texture_view.is_some() || self.shared_color_buffer.is_some() {
+            for layer in layers.iter_mut().filter(|layer| layer.shares_colors()) {
+                layer.lines.update_color_bindings(
+                    device,
+                    queue,
+                    self.shared_color_texture_view.clone(),
+                    self.shared_color_buffer.clone(),
+                );
+            }
+        }
+
+        layers
+    }
+
+    fn create_composite(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        exposure: &render::exposure::Context,
+    ) -> render::composite::Context {
+        let mut composite = render::composite::Context::new(
+            device,
+            queue,
+            self.swapchain_format,
+            exposure,
+            &self.background,
+            &self.settings,
+        );
+        composite.set_safe_zones(self.safe_zones);
+        composite
+    }
+
+    /// The lines are drawn into the offscreen scene when there is one.
+    fn get_lines_format(&self) -> wgpu::TextureFormat {
+        if self.exposure.is_some() {
+            render::exposure::SCENE_FORMAT
+        } else {
+            self.swapchain_format
+        }
+    }
+
+    fn set_lines_target_format(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target_format: wgpu::TextureFormat,
+    ) {
+        self.lines.set_target_format(device, queue, target_format);
+        for layer in &mut self.layers {
+            layer.lines.set_target_format(device, queue, target_format);
+        }
+        if let Some(particles) = &mut self.particles {
+            particles.set_target_format(device, queue, target_format);
+        }
+        if let Some(dye) = &mut self.dye {
+            dye.set_target_format(device, queue, target_format);
+        }
+    }
+
+    /// Present to a surface of a different format, for example when switching
+    /// between SDR and HDR surfaces.
+    pub fn set_swapchain_format(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        swapchain_format: wgpu::TextureFormat,
+    ) {
+        self.swapchain_format = swapchain_format;
+        self.debug_texture =
+            create_debug_texture(device, swapchain_format, &self.fluid, &self.noise_generator);
+        self.fade.set_target_format(device, swapchain_format);
+
+        if self.exposure.is_some() {
+            let exposure = render::exposure::Context::new(
+                device,
+                swapchain_format,
+                self.scene_size,
+                &self.settings,
+            );
+            self.composite = Some(self.create_composite(device, queue, &exposure));
+            self.exposure = Some(exposure);
+        } else {
+            self.set_lines_target_format(device, queue, swapchain_format);
+        }
+
+        if let (Some(exposure), Some(bloom)) = (&self.exposure, &mut self.bloom) {
+            bloom.resize(device, self.scene_size, exposure.get_scene_texture_view());
+        }
+    }
+
+    /// Set the parts of the screen covered by system UI. The lines behind them
+    /// are dimmed by `Settings::safe_zone_dimming`.
+    pub fn set_safe_zones(&mut self, safe_zones: render::SafeZones) {
+        self.safe_zones = safe_zones;
+        if let Some(composite) = &mut self.composite {
+            composite.set_safe_zones(safe_zones);
+        }
+    }
+
+    /// Resize the offscreen targets to match the scene size.
+    fn resize_scene(&mut self, device: &wgpu::Device) {
+        if let Some(exposure) = &mut self.exposure {
+            exposure.resize(device, self.scene_size);
+
+            if let Some(composite) = &mut self.composite {
+                composite.resize(device, exposure.get_scene_texture_view());
+            }
+
+            if let Some(bloom) = &mut self.bloom {
+                bloom.resize(device, self.scene_size, exposure.get_scene_texture_view());
+            }
+        }
+
+        if let Some(trails) = &mut self.trails {
+            trails.resize(device, self.scene_size);
+        }
+
+        if let Some(msaa) = &mut self.msaa {
+            msaa.resize(device, self.scene_size);
+        }
+    }
+
+    fn bind_shared_colors(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.shared_color_texture_view.is_none() && self.shared_color_buffer.is_none() {
+            return;
+        }
+
+        for layer in self.layers.iter_mut().filter(|layer| layer.shares_colors()) {
+            layer.lines.update_color_bindings(
+                device,
+                queue,
+                self.shared_color_texture_view.clone(),
+                self.shared_color_buffer.clone(),
+            );
+        }
+        self.lines.update_color_bindings(
+            device,
+            queue,
+            self.shared_color_texture_view.clone(),
+            self.shared_color_buffer.clone(),
+        );
     }
 
     pub fn sample_colors_from_image(
@@ -55,14 +480,53 @@ impl Flux {
         self.sample_colors_from_texture_view(device, queue, texture_view);
     }
 
+    /// Sample colors from an animation, uploading a new frame to the color
+    /// texture as it advances.
+    pub fn sample_colors_from_animation(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        animation: render::color::Animation,
+    ) {
+        let color_animation = render::color::AnimatedTexture::new(device, queue, animation);
+        self.sample_colors_from_texture_view(device, queue, color_animation.create_view());
+        self.color_animation = Some(color_animation);
+    }
+
     pub fn sample_colors_from_texture_view(
         &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         texture_view: wgpu::TextureView,
     ) {
+        self.color_animation = None;
+        for layer in self.layers.iter_mut().filter(|layer| layer.shares_colors()) {
+            layer
+                .lines
+                .update_color_bindings(device, queue, Some(texture_view.clone()), None);
+        }
+        self.lines
+            .update_color_bindings(device, queue, Some(texture_view.clone()), None);
+        self.shared_color_texture_view = Some(texture_view);
+        self.shared_color_buffer = None;
+    }
+
+    /// Sample colors from a buffer of RGBA colors, like the color presets.
+    pub fn sample_colors_from_buffer(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color_buffer: wgpu::Buffer,
+    ) {
+        for layer in self.layers.iter_mut().filter(|layer| layer.shares_colors()) {
+            layer
+                .lines
+                .update_color_bindings(device, queue, None, Some(color_buffer.clone()));
+        }
         self.lines
-            .update_color_bindings(device, queue, Some(texture_view), None);
+            .update_color_bindings(device, queue, None, Some(color_buffer.clone()));
+        self.shared_color_texture_view = None;
+        self.shared_color_buffer = Some(color_buffer);
     }
 
     pub fn new(
@@ -93,18 +557,66 @@ impl Flux {
         log::info!("📐 Logical size: {}x{}", logical_width, logical_height);
         log::info!("📏 Physical size: {}x{}", physical_width, physical_height);
 
-        let grid = grid::Grid::new(logical_width, logical_height, settings.grid_spacing);
+        let grid = grid::Grid::new(logical_width, logical_height, settings);
 
         let fluid = render::fluid::Context::new(device, queue, grid.scaling_ratio, settings);
 
-        let lines = render::lines::Context::new(
-            device,
-            queue,
-            swapchain_format,
-            logical_size,
-            &grid,
-            settings,
-        );
+        let background = render::background::Context::new(device, queue, settings);
+
+        let scene_size = get_scene_size(device, physical_size, settings);
+
+        let exposure = uses_scene_target(settings).then(|| {
+            render::exposure::Context::new(device, swapchain_format, scene_size, settings)
+        });
+
+        let composite = exposure.as_ref().map(|exposure| {
+            render::composite::Context::new(
+                device,
+                queue,
+                swapchain_format,
+                exposure,
+                &background,
+                settings,
+            )
+        });
+
+        let bloom = exposure
+            .as_ref()
+            .filter(|_| settings.bloom_intensity > 0.0)
+            .map(|exposure| {
+                render::bloom::Context::new(
+                    device,
+                    scene_size,
+                    exposure.get_scene_texture_view(),
+                    settings,
+                )
+            });
+
+        let trails = render::trails::is_enabled(settings)
+            .then(|| render::trails::Context::new(device, scene_size, settings));
+
+        let fade = render::fade::Context::new(device, swapchain_format, physical_size, settings);
+
+        let msaa = exposure
+            .as_ref()
+            .filter(|_| render::msaa::get_sample_count(settings) > 1)
+            .map(|_| render::msaa::Context::new(device, scene_size));
+
+        let lines_format = if exposure.is_some() {
+            render::exposure::SCENE_FORMAT
+        } else {
+            swapchain_format
+        };
+
+        let lines =
+            render::lines::Context::new(device, queue, lines_format, logical_size, &grid, settings);
+        let layers = layer::create_layers(device, queue, lines_format, logical_size, settings);
+
+        let particles = (settings.mode == settings::Mode::Particles)
+            .then(|| render::particles::Context::new(device, lines_format, logical_size, settings));
+        let dye = (settings.mode == settings::Mode::Dye).then(|| {
+            render::dye::Context::new(device, lines_format, fluid.get_fluid_size(), settings)
+        });
 
         let mut noise_generator_builder = render::noise::NoiseGeneratorBuilder::new(
             2 * settings.fluid_size,
@@ -116,33 +628,47 @@ impl Flux {
         });
         let noise_generator = noise_generator_builder.build(device, queue);
 
-        let debug_texture = render::texture::Context::new(
-            device,
-            swapchain_format,
-            &[
-                ("fluid", fluid.get_velocity_texture_view()),
-                ("noise", noise_generator.get_noise_texture_view()),
-                ("pressure", fluid.get_pressure_texture_view()),
-                ("divergence", fluid.get_divergence_texture_view()),
-            ],
-        );
+        let debug_texture =
+            create_debug_texture(device, swapchain_format, &fluid, &noise_generator);
 
         Ok(Flux {
             settings: Arc::clone(settings),
+            swapchain_format,
             logical_size,
             physical_size,
+            scene_size,
 
             fluid,
             grid,
             lines,
+            layers,
+            shared_color_texture_view: None,
+            shared_color_buffer: None,
             noise_generator,
             debug_texture,
+            exposure,
+            composite,
+            background,
+            bloom,
+            trails,
+            fade,
+            particles,
+            dye,
+            msaa,
             color_image: Arc::new(Mutex::new(None)),
+            color_animation: None,
 
             last_timestamp: 0.0,
             elapsed_time: 0.0,
 
             fluid_frame_time: 0.0,
+
+            view_angle: 0.0,
+            camera: camera::Camera::new(),
+            safe_zones: render::SafeZones::default(),
+            tween: None,
+            lines_reset_pending: false,
+            layers_reset_pending: false,
         })
     }
 
@@ -155,9 +681,6 @@ impl Flux {
         physical_width: u32,
         physical_height: u32,
     ) {
-        let grid = grid::Grid::new(logical_width, logical_height, self.settings.grid_spacing);
-
-        // TODO: fetch line state from GPU and resample for new grid
         let logical_size = wgpu::Extent3d {
             width: logical_width,
             height: logical_height,
@@ -169,19 +692,17 @@ impl Flux {
             depth_or_array_layers: 1,
         };
 
-        self.lines
-            .resize(device, queue, logical_size, &grid, &self.settings);
+        if let Some(particles) = &mut self.particles {
+            particles.resize(queue, logical_size, &self.settings);
+        }
 
-        self.grid = grid;
         self.logical_size = logical_size;
         self.physical_size = physical_size;
+        self.scene_size = get_scene_size(device, physical_size, &self.settings);
+        self.resize_scene(device);
 
-        // self.fluid.resize(device, self.grid.scaling_ratio);
-        self.noise_generator.resize(
-            device,
-            2 * self.settings.fluid_size,
-            self.grid.scaling_ratio,
-        );
+        // TODO: fetch line state from GPU and resample for new grid
+        self.reset_lines(device, queue, true);
     }
 
     pub fn animate(
@@ -212,8 +733,33 @@ impl Flux {
 
         self.last_timestamp = timestamp;
         self.elapsed_time += timestep;
+
+        // The frame before has been kept to crossfade from
+        if (self.lines_reset_pending || self.layers_reset_pending)
+            && !self.fade.is_snapshot_pending()
+        {
+            self.lay_out_lines(device, queue);
+        }
+
+        if let Some(mut tween) = self.tween.take() {
+            tween.tick(timestep);
+            if tween.is_finished() {
+                self.apply_settings(device, queue, tween.get_target());
+                // The settings reset the colors, so bind any sampled since the
+                // tween started again
+                self.bind_shared_colors(device, queue);
+            } else {
+                self.apply_eased_settings(device, queue, &Arc::new(tween.get_settings()));
+                self.tween = Some(tween);
+            }
+        }
         self.fluid_frame_time += timestep;
 
+        self.view_angle = (self.view_angle + self.settings.rotation_speed * timestep) % 360.0;
+        if let Some(drift) = &self.settings.camera_drift {
+            self.camera.tick(timestep, drift);
+        }
+
         // Reset animation timers to avoid precision issues
         let timer_overflow = self.elapsed_time - MAX_ELAPSED_TIME;
         if timer_overflow >= 0.0 {
@@ -250,22 +796,67 @@ impl Flux {
             self.fluid_frame_time -= self.settings.fluid_timestep;
         }
 
-        {
+        if let Some(color_animation) = &mut self.color_animation {
+            color_animation.tick(queue, timestep);
+        }
+
+        if let Some(exposure) = &mut self.exposure {
+            exposure.tick(queue, timestep);
+        }
+
+        if let Some(composite) = &mut self.composite {
+            composite.tick(queue);
+        }
+
+        if let Some(trails) = &mut self.trails {
+            trails.tick(timestep);
+        }
+
+        self.fade.tick(timestep);
+
+        if let Some(particles) = &mut self.particles {
+            particles.tick(queue, timestep);
+
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("flux::advect_particles"),
+                timestamp_writes: None,
+            });
+            particles.advect(&mut cpass, self.fluid.get_read_velocity_bind_group());
+        }
+
+        if let Some(dye) = &mut self.dye {
+            dye.tick(queue, timestep);
+
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("flux::advect_dye"),
+                timestamp_writes: None,
+            });
+            dye.advect(&mut cpass, self.fluid.get_read_velocity_bind_group());
+        }
+
+        // The lines are only drawn in the normal mode
+        if self.settings.mode == settings::Mode::Normal {
             self.lines
                 .tick_line_uniforms(device, queue, timestep, self.elapsed_time);
+            for layer in &mut self.layers {
+                layer.tick(device, queue, timestep, self.elapsed_time);
+            }
 
             let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
                 label: Some("flux::place_lines"),
                 timestamp_writes: None,
             });
 
-            self.lines
-                .place_lines(&mut cpass, self.fluid.get_read_velocity_bind_group());
+            let velocity_bind_group = self.fluid.get_read_velocity_bind_group();
+            for layer in &mut self.layers {
+                layer.lines.place_lines(&mut cpass, velocity_bind_group);
+            }
+            self.lines.place_lines(&mut cpass, velocity_bind_group);
         }
     }
 
     pub fn render(
-        &self,
+        &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         encoder: &mut wgpu::CommandEncoder,
@@ -274,14 +865,41 @@ impl Flux {
     ) {
         encoder.push_debug_group("render lines");
 
+        // Exposure and tone mapping only apply to the visualizations, not the debug views
+        let exposure = match self.settings.mode {
+            settings::Mode::Normal | settings::Mode::Particles | settings::Mode::Dye => {
+                self.exposure.as_ref()
+            }
+            _ => None,
+        };
+        let trails = exposure.and(self.trails.as_ref());
+        let msaa = exposure.and(self.msaa.as_ref());
+
+        // Trails draw on top of the previous frames, and are then copied to the scene
+        let (target_view, load) = match (trails, exposure) {
+            (Some(trails), _) => (trails.get_accumulation_texture_view(), wgpu::LoadOp::Load),
+            (None, Some(exposure)) => (
+                exposure.get_scene_texture_view(),
+                wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            ),
+            (None, None) => (view, wgpu::LoadOp::Clear(wgpu::Color::BLACK)),
+        };
+
+        // Multisample into a separate target, then resolve. With trails, the
+        // multisampled target keeps the previous frames.
+        let (target_view, resolve_target) = match msaa {
+            Some(msaa) => (msaa.get_texture_view(), Some(target_view)),
+            None => (target_view, None),
+        };
+
         {
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("flux::render"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view,
-                    resolve_target: None,
+                    view: target_view,
+                    resolve_target,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        load,
                         store: wgpu::StoreOp::Store,
                     },
                     depth_slice: None,
@@ -291,17 +909,57 @@ impl Flux {
                 occlusion_query_set: None,
             });
 
+            let (zoom, pan) = match &self.settings.camera_drift {
+                Some(drift) => self.camera.get_view(drift),
+                None => (1.0, [0.0, 0.0]),
+            };
+            let view_transform = render::ViewTransform {
+                zoom,
+                pan,
+                rotation: (self.settings.view_rotation + self.view_angle).to_radians(),
+                mirror: [
+                    self.settings.mirror_horizontal,
+                    self.settings.mirror_vertical,
+                ],
+                aspect: self.physical_size.width as f32 / self.physical_size.height.max(1) as f32,
+                ..screen_viewport
+                    .map(|ref sv| {
+                        render::ViewTransform::from_screen_viewport(&self.physical_size, sv)
+                    })
+                    .unwrap_or_default()
+            };
+
             use settings::Mode::*;
             match &self.settings.mode {
                 Normal => {
-                    let view_transform = screen_viewport
-                        .map(|ref sv| {
-                            render::ViewTransform::from_screen_viewport(&self.physical_size, sv)
-                        })
-                        .unwrap_or_default();
-                    self.lines.set_view_transform(queue, view_transform);
-                    self.lines.draw_lines(&mut rpass);
-                    self.lines.draw_endpoints(&mut rpass);
+                    if let Some(trails) = trails {
+                        trails.fade(&mut rpass);
+                    }
+                    let draw_endpoints =
+                        self.settings.endpoint_style != settings::EndpointStyle::None;
+                    let layers = self.layers.iter().map(|layer| &layer.lines);
+                    for lines in layers.chain(std::iter::once(&self.lines)) {
+                        lines.set_view_transform(queue, view_transform);
+                        lines.draw_lines(&mut rpass);
+                        if draw_endpoints {
+                            lines.draw_endpoints(&mut rpass);
+                        }
+                    }
+                }
+                Particles => {
+                    if let Some(trails) = trails {
+                        trails.fade(&mut rpass);
+                    }
+                    if let Some(particles) = &self.particles {
+                        particles.set_view_transform(queue, view_transform);
+                        particles.draw(&mut rpass);
+                    }
+                }
+                Dye => {
+                    if let Some(dye) = &self.dye {
+                        dye.set_view_transform(queue, view_transform);
+                        dye.draw(&mut rpass);
+                    }
                 }
                 DebugNoise => {
                     self.debug_texture.draw_texture(device, &mut rpass, "noise");
@@ -320,10 +978,138 @@ impl Flux {
             };
         }
 
+        if let (Some(trails), Some(exposure)) = (trails, exposure) {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("flux::blit_trails"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: exposure.get_scene_texture_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            trails.draw(&mut rpass);
+        }
+
+        if let (Some(exposure), Some(composite)) = (exposure, &self.composite) {
+            if let Some(bloom) = &self.bloom {
+                bloom.draw(encoder, exposure.get_scene_texture_view());
+            }
+
+            if exposure.has_auto_exposure() {
+                let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("flux::measure_exposure"),
+                    timestamp_writes: None,
+                });
+                exposure.measure(&mut cpass);
+            }
+
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("flux::composite"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            composite.draw(&mut rpass, exposure, &self.background);
+        }
+
         encoder.pop_debug_group();
+
+        if self.fade.is_active() {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("flux::fade"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.fade.draw(&mut rpass);
+        }
+
+        // Keep what was presented, to crossfade from before the lines are reset
+        self.fade.capture(device, encoder, view.texture());
+    }
+}
+
+fn uses_scene_target(settings: &Settings) -> bool {
+    settings.hdr
+        || settings.auto_exposure.is_some()
+        || settings.color_space != settings::ColorSpace::Srgb
+        || settings.bloom_intensity > 0.0
+        || render::trails::is_enabled(settings)
+        || settings.dither
+        || settings.film_grain > 0.0
+        || settings.background != settings::Background::Black
+        || settings.antialiasing != settings::Antialiasing::None
+        || settings.render_scale < 1.0
+        || settings.kaleidoscope.is_some()
+        || settings.vignette > 0.0
+        || settings.safe_zone_dimming > 0.0
+}
+
+fn get_scene_size(
+    device: &wgpu::Device,
+    physical_size: wgpu::Extent3d,
+    settings: &Settings,
+) -> wgpu::Extent3d {
+    let supersample = match settings.antialiasing {
+        settings::Antialiasing::Supersample(scale) => scale.clamp(1.0, 4.0),
+        _ => 1.0,
+    };
+    let scale = supersample * settings.render_scale.clamp(MIN_RENDER_SCALE, 1.0);
+
+    let max_size = device.limits().max_texture_dimension_2d;
+    let scale_dimension =
+        |dimension: u32| ((dimension as f32 * scale).round() as u32).clamp(1, max_size);
+
+    wgpu::Extent3d {
+        width: scale_dimension(physical_size.width),
+        height: scale_dimension(physical_size.height),
+        depth_or_array_layers: 1,
     }
 }
 
+fn create_debug_texture(
+    device: &wgpu::Device,
+    swapchain_format: wgpu::TextureFormat,
+    fluid: &render::fluid::Context,
+    noise_generator: &render::noise::NoiseGenerator,
+) -> render::texture::Context {
+    render::texture::Context::new(
+        device,
+        swapchain_format,
+        &[
+            ("fluid", fluid.get_velocity_texture_view()),
+            ("noise", noise_generator.get_noise_texture_view()),
+            ("pressure", fluid.get_pressure_texture_view()),
+            ("divergence", fluid.get_divergence_texture_view()),
+        ],
+    )
+}
+
 // #[derive(Debug)]
 // pub enum Problem {
 //     ReadSettings(String),
@@ -342,3 +1128,79 @@ impl Flux {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Tests that draw need a device like the one the apps use. Skip them
+    // where there is none, like on CI machines without a GPU.
+    fn request_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::PRIMARY,
+            ..Default::default()
+        });
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default())).ok()?;
+
+        let mut limits = wgpu::Limits::default().using_resolution(adapter.limits());
+        limits.max_push_constant_size = 8;
+        let features = wgpu::Features::PUSH_CONSTANTS
+            | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+            | wgpu::Features::FLOAT32_FILTERABLE;
+
+        pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            required_features: features,
+            required_limits: limits,
+            ..Default::default()
+        }))
+        .ok()
+    }
+
+    #[test]
+    fn keeps_sampled_colors_through_a_tween() {
+        let Some((device, queue)) = request_device() else {
+            eprintln!("Skipping: no GPU");
+            return;
+        };
+
+        let settings = Arc::new(Settings::default());
+        let mut flux = Flux::new(
+            &device,
+            &queue,
+            wgpu::TextureFormat::Bgra8Unorm,
+            320,
+            180,
+            320,
+            180,
+            &settings,
+        )
+        .unwrap();
+
+        // Like the desktop app, sample a custom color wheel after each update
+        let new_settings = Arc::new(Settings {
+            line_width: 2.0 * settings.line_width,
+            ..(*settings).clone()
+        });
+        flux.update(&device, &queue, &new_settings);
+        let color_wheel = settings::COLOR_SCHEME_PLASMA;
+        let color_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 4 * color_wheel.len() as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&color_buffer, 0, bytemuck::cast_slice(&color_wheel));
+        flux.sample_colors_from_buffer(&device, &queue, color_buffer);
+
+        // Step past the end of the tween
+        let frame_count = (new_settings.tween_duration / MAX_FRAME_TIME).ceil() as u32 + 1;
+        for frame in 1..=frame_count {
+            let mut encoder = device.create_command_encoder(&Default::default());
+            let timestamp = 1000.0 * (frame as f32 * MAX_FRAME_TIME) as f64;
+            flux.compute(&device, &queue, &mut encoder, timestamp);
+            queue.submit(Some(encoder.finish()));
+            assert_eq!(flux.lines.get_line_color_mode(), 1);
+        }
+        assert!(flux.tween.is_none());
+    }
+}
@@ -1,3 +1,28 @@
+use crate::render::color::Problem;
+use crate::rng;
+use crate::settings::{GridLayout, LineDensity, Settings};
+
+use image::GrayImage;
+use rand::Rng;
+use std::path::Path;
+
+// How far a jittered point can move, as a fraction of the grid spacing
+const JITTER: f32 = 0.8;
+
+// The minimum distance between random points, relative to the grid spacing.
+// Poisson disk sampling packs less tightly than a grid, so this keeps the
+// line count close to that of a rectangular grid.
+const POISSON_DISK_RADIUS: f32 = 0.85;
+const POISSON_DISK_ATTEMPTS: u32 = 30;
+
+// Keep a few lines everywhere, so that the grid is never empty
+const MIN_DENSITY: f32 = 0.05;
+const MAX_DENSITY_IMAGE_SIZE: u32 = 512;
+
+// Limit how much the lines are adjusted to balance out the density
+const MIN_WEIGHT: f32 = 0.5;
+const MAX_WEIGHT: f32 = 2.0;
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct ScalingRatio {
     x: f32,
@@ -37,14 +62,50 @@ pub struct Grid {
     pub line_count: u32,
     pub scaling_ratio: ScalingRatio,
     pub basepoints: Vec<f32>,
+    /// Scales the width and brightness of each line to balance out the density
+    pub line_weights: Vec<f32>,
 }
 
 impl Grid {
-    pub fn new(uwidth: u32, uheight: u32, grid_spacing: u32) -> Self {
+    /// Lay out the grid, spacing the lines further apart if there would be more
+    /// than its share of `max_lines`.
+    pub fn new(uwidth: u32, uheight: u32, settings: &Settings) -> Self {
+        let max_lines = get_line_budget(settings, settings.grid_spacing);
+        let mut grid_spacing = settings.grid_spacing as f32;
+        // Stop before there's less than one cell across the screen
+        let max_grid_spacing = uwidth.min(uheight) as f32;
+
+        loop {
+            let grid = Self::with_spacing(uwidth, uheight, grid_spacing, settings);
+
+            match max_lines {
+                Some(max_lines)
+                    if grid.line_count > max_lines && grid_spacing < max_grid_spacing =>
+                {
+                    // The line count falls with the square of the spacing
+                    let ratio = grid.line_count as f32 / max_lines as f32;
+                    grid_spacing = (grid_spacing * ratio.sqrt().max(1.01)).min(max_grid_spacing);
+                }
+                _ => {
+                    if grid_spacing > settings.grid_spacing as f32 {
+                        log::info!(
+                            "Line budget of {} reached. Increased the grid spacing from {} to {:.1} ({} lines)",
+                            max_lines.unwrap_or_default(),
+                            settings.grid_spacing,
+                            grid_spacing,
+                            grid.line_count,
+                        );
+                    }
+                    return grid;
+                }
+            }
+        }
+    }
+
+    fn with_spacing(uwidth: u32, uheight: u32, grid_spacing: f32, settings: &Settings) -> Self {
         let height = uheight as f32;
         let width = uwidth as f32;
         let aspect_ratio = width / height;
-        let grid_spacing = grid_spacing as f32;
 
         let columns = f32::floor(width / grid_spacing);
         let rows = f32::floor((height / width) * columns);
@@ -53,17 +114,32 @@ impl Grid {
 
         let columns = columns as u32 + 1;
         let rows = rows as u32 + 1;
-        let line_count = rows * columns;
         let scaling_ratio = ScalingRatio::new(columns, rows);
 
-        let mut basepoints = Vec::with_capacity(2 * line_count as usize);
-
-        for v in 0..rows {
-            for u in 0..columns {
-                basepoints.push(u as f32 * grid_spacing_x);
-                basepoints.push(v as f32 * grid_spacing_y);
+        let basepoints = match settings.grid_layout {
+            GridLayout::Rectangular => {
+                rectangular_basepoints(columns, rows, grid_spacing_x, grid_spacing_y)
             }
-        }
+            GridLayout::Hexagonal => hexagonal_basepoints(grid_spacing_x, aspect_ratio),
+            GridLayout::Jittered => jittered_basepoints(
+                columns,
+                rows,
+                grid_spacing_x,
+                grid_spacing_y,
+                &mut rng::seeded("grid"),
+            ),
+            GridLayout::PoissonDisk => {
+                poisson_disk_basepoints(grid_spacing_x, aspect_ratio, &mut rng::seeded("grid"))
+            }
+        };
+
+        let density_map = DensityMap::new(&settings.line_density);
+        let (basepoints, line_weights) =
+            thin_basepoints(basepoints, &density_map, &mut rng::seeded("density"));
+
+        // The brightness is normalized by the line count, so count what was
+        // actually placed.
+        let line_count = (basepoints.len() / 2) as u32;
 
         Self {
             width: uwidth,
@@ -74,8 +150,275 @@ impl Grid {
             scaling_ratio,
             line_count,
             basepoints,
+            line_weights,
+        }
+    }
+}
+
+/// How much of the grid to keep in each part of the screen
+enum DensityMap {
+    Uniform,
+    Radial { center: f32, edge: f32 },
+    Image(GrayImage),
+}
+
+impl DensityMap {
+    fn new(line_density: &LineDensity) -> Self {
+        match line_density {
+            LineDensity::Uniform => Self::Uniform,
+            LineDensity::Radial { center, edge } => Self::Radial {
+                center: *center,
+                edge: *edge,
+            },
+            LineDensity::Image(path) => match load_density_image(path) {
+                Ok(image) => Self::Image(image),
+                Err(err) => {
+                    log::error!(
+                        "Failed to load density map from {}: {}",
+                        path.display(),
+                        err
+                    );
+                    Self::Uniform
+                }
+            },
+        }
+    }
+
+    /// Sample the density at a basepoint. The origin is the bottom-left corner
+    /// of the screen.
+    fn sample(&self, u: f32, v: f32) -> f32 {
+        let density = match self {
+            Self::Uniform => 1.0,
+            Self::Radial { center, edge } => {
+                // 0.0 at the center and 1.0 in the corners
+                let distance = ((2.0 * u - 1.0).powi(2) + (2.0 * v - 1.0).powi(2)).sqrt()
+                    / std::f32::consts::SQRT_2;
+                let t = distance * distance * (3.0 - 2.0 * distance);
+                center + (edge - center) * t
+            }
+            Self::Image(image) => {
+                let x = (u * (image.width() - 1) as f32).round() as u32;
+                let y = ((1.0 - v) * (image.height() - 1) as f32).round() as u32;
+                image.get_pixel(x, y)[0] as f32 / 255.0
+            }
+        };
+
+        density.clamp(MIN_DENSITY, 1.0)
+    }
+}
+
+/// The share of `max_lines` for a grid with the given spacing.
+///
+/// The main lines and the layers draw from one budget. Each grid gets a share
+/// proportional to the number of lines it would have, which falls with the
+/// square of its spacing, so every grid is thinned out by the same factor.
+pub fn get_line_budget(settings: &Settings, grid_spacing: u32) -> Option<u32> {
+    let density = |spacing: u32| 1.0 / (spacing.max(1) as f32).powi(2);
+    let total_density = density(settings.grid_spacing)
+        + settings
+            .layers
+            .iter()
+            .map(|layer| density(layer.grid_spacing))
+            .sum::<f32>();
+
+    settings
+        .max_lines
+        .map(|max_lines| (max_lines as f32 * density(grid_spacing) / total_density).floor() as u32)
+}
+
+fn load_density_image(path: &Path) -> Result<GrayImage, Problem> {
+    let encoded_bytes = std::fs::read(path).map_err(Problem::ReadImage)?;
+    let mut img = image::load_from_memory(&encoded_bytes).map_err(Problem::DecodeColorTexture)?;
+
+    // There's no need for more detail than the grid has
+    if u32::max(img.width(), img.height()) > MAX_DENSITY_IMAGE_SIZE {
+        img = img.resize(
+            MAX_DENSITY_IMAGE_SIZE,
+            MAX_DENSITY_IMAGE_SIZE,
+            image::imageops::FilterType::Triangle,
+        );
+    }
+
+    Ok(img.to_luma8())
+}
+
+/// Randomly drop basepoints where the density is low, and weigh the remaining
+/// lines by how sparse their surroundings are.
+fn thin_basepoints(
+    basepoints: Vec<f32>,
+    density_map: &DensityMap,
+    rng: &mut impl Rng,
+) -> (Vec<f32>, Vec<f32>) {
+    if let DensityMap::Uniform = density_map {
+        let line_weights = vec![1.0; basepoints.len() / 2];
+        return (basepoints, line_weights);
+    }
+
+    let total = basepoints.len() / 2;
+    let mut kept = Vec::with_capacity(basepoints.len());
+    let mut densities = Vec::with_capacity(total);
+
+    for point in basepoints.chunks_exact(2) {
+        let density = density_map.sample(point[0], point[1]);
+        if rng.random::<f32>() < density {
+            kept.extend_from_slice(point);
+            densities.push(density);
+        }
+    }
+
+    // Lines at the average density are left as they are
+    let mean_density = densities.len() as f32 / total as f32;
+    let line_weights = densities
+        .iter()
+        .map(|density| {
+            (mean_density / density)
+                .sqrt()
+                .clamp(MIN_WEIGHT, MAX_WEIGHT)
+        })
+        .collect();
+
+    (kept, line_weights)
+}
+
+fn rectangular_basepoints(
+    columns: u32,
+    rows: u32,
+    grid_spacing_x: f32,
+    grid_spacing_y: f32,
+) -> Vec<f32> {
+    let mut basepoints = Vec::with_capacity(2 * (rows * columns) as usize);
+
+    for v in 0..rows {
+        for u in 0..columns {
+            basepoints.push(u as f32 * grid_spacing_x);
+            basepoints.push(v as f32 * grid_spacing_y);
+        }
+    }
+
+    basepoints
+}
+
+/// Pack the points into equilateral triangles. Every other row is shifted by
+/// half a column, and the rows are closer together to keep the spacing even.
+fn hexagonal_basepoints(grid_spacing_x: f32, aspect_ratio: f32) -> Vec<f32> {
+    let row_spacing = 0.5 * 3.0_f32.sqrt() * grid_spacing_x * aspect_ratio;
+    let rows = (1.0 / row_spacing).floor() as u32 + 1;
+
+    let mut basepoints = Vec::new();
+
+    for v in 0..rows {
+        let offset = if v % 2 == 1 {
+            0.5 * grid_spacing_x
+        } else {
+            0.0
+        };
+        let mut u = offset;
+        while u <= 1.0 + 1e-4 {
+            basepoints.push(u.min(1.0));
+            basepoints.push(v as f32 * row_spacing);
+            u += grid_spacing_x;
+        }
+    }
+
+    basepoints
+}
+
+/// Nudge each point of a rectangular grid by up to half the distance to its
+/// neighbours.
+fn jittered_basepoints(
+    columns: u32,
+    rows: u32,
+    grid_spacing_x: f32,
+    grid_spacing_y: f32,
+    rng: &mut impl Rng,
+) -> Vec<f32> {
+    let mut basepoints = rectangular_basepoints(columns, rows, grid_spacing_x, grid_spacing_y);
+
+    for point in basepoints.chunks_exact_mut(2) {
+        point[0] += JITTER * grid_spacing_x * (rng.random::<f32>() - 0.5);
+        point[1] += JITTER * grid_spacing_y * (rng.random::<f32>() - 0.5);
+        point[0] = point[0].clamp(0.0, 1.0);
+        point[1] = point[1].clamp(0.0, 1.0);
+    }
+
+    basepoints
+}
+
+/// Scatter points with Bridson's algorithm, "Fast Poisson Disk Sampling in
+/// Arbitrary Dimensions". New points are tried in a ring around existing ones
+/// until none fit.
+///
+/// The points are placed in screen-shaped space, where the height is 1.0, so
+/// that the spacing is the same in both directions.
+fn poisson_disk_basepoints(grid_spacing_x: f32, aspect_ratio: f32, rng: &mut impl Rng) -> Vec<f32> {
+    let width = aspect_ratio;
+    let height = 1.0;
+    let radius = POISSON_DISK_RADIUS * grid_spacing_x * aspect_ratio;
+
+    // Each cell can hold at most one point
+    let cell_size = radius / std::f32::consts::SQRT_2;
+    let cell_columns = (width / cell_size).ceil() as usize;
+    let cell_rows = (height / cell_size).ceil() as usize;
+    let mut cells: Vec<Option<usize>> = vec![None; cell_columns * cell_rows];
+
+    let cell_index = |x: f32, y: f32| -> (usize, usize) {
+        (
+            ((x / cell_size) as usize).min(cell_columns - 1),
+            ((y / cell_size) as usize).min(cell_rows - 1),
+        )
+    };
+
+    let mut points: Vec<(f32, f32)> = Vec::new();
+    let mut active: Vec<usize> = Vec::new();
+
+    let first = (width * rng.random::<f32>(), height * rng.random::<f32>());
+    let (cx, cy) = cell_index(first.0, first.1);
+    cells[cy * cell_columns + cx] = Some(0);
+    points.push(first);
+    active.push(0);
+
+    while !active.is_empty() {
+        let active_index = rng.random_range(0..active.len());
+        let (px, py) = points[active[active_index]];
+
+        let candidate = (0..POISSON_DISK_ATTEMPTS).find_map(|_| {
+            let angle = std::f32::consts::TAU * rng.random::<f32>();
+            let distance = radius * (1.0 + rng.random::<f32>());
+            let (x, y) = (px + distance * angle.cos(), py + distance * angle.sin());
+
+            if !(0.0..=width).contains(&x) || !(0.0..=height).contains(&y) {
+                return None;
+            }
+
+            let (cx, cy) = cell_index(x, y);
+            let is_clear = (cy.saturating_sub(2)..(cy + 3).min(cell_rows)).all(|ny| {
+                (cx.saturating_sub(2)..(cx + 3).min(cell_columns)).all(|nx| {
+                    cells[ny * cell_columns + nx].is_none_or(|other| {
+                        let (ox, oy) = points[other];
+                        (ox - x).powi(2) + (oy - y).powi(2) >= radius * radius
+                    })
+                })
+            });
+
+            is_clear.then_some((x, y, cx, cy))
+        });
+
+        match candidate {
+            Some((x, y, cx, cy)) => {
+                cells[cy * cell_columns + cx] = Some(points.len());
+                active.push(points.len());
+                points.push((x, y));
+            }
+            None => {
+                active.swap_remove(active_index);
+            }
         }
     }
+
+    points
+        .into_iter()
+        .flat_map(|(x, y)| [x / width, y / height])
+        .collect()
 }
 
 fn clamp_logical_size(width: u32, height: u32) -> (u32, u32) {
@@ -107,9 +450,20 @@ mod test {
         }
     }
 
+    fn create_settings(grid_spacing: u32, grid_layout: GridLayout) -> Settings {
+        Settings {
+            grid_spacing,
+            grid_layout,
+            ..Default::default()
+        }
+    }
+
     fn create_test_grid(logical_size: LogicalSize, grid_spacing: u32) -> (u32, u32) {
-        let Grid { columns, rows, .. } =
-            Grid::new(logical_size.width, logical_size.height, grid_spacing);
+        let Grid { columns, rows, .. } = Grid::new(
+            logical_size.width,
+            logical_size.height,
+            &create_settings(grid_spacing, GridLayout::Rectangular),
+        );
         (columns, rows)
     }
 
@@ -172,4 +526,132 @@ mod test {
             (logical_size.width, logical_size.height)
         );
     }
+
+    fn assert_in_bounds(grid: &Grid) {
+        assert_eq!(grid.line_count as usize, grid.basepoints.len() / 2);
+        assert!(grid.basepoints.iter().all(|p| (0.0..=1.0).contains(p)));
+    }
+
+    #[test]
+    fn offsets_every_other_row_in_hexagonal_grid() {
+        let grid = Grid::new(1280, 800, &create_settings(15, GridLayout::Hexagonal));
+        assert_in_bounds(&grid);
+
+        let spacing = 1.0 / f32::floor(1280.0 / 15.0);
+        let second_row = grid
+            .basepoints
+            .chunks_exact(2)
+            .find(|point| point[1] > 0.0)
+            .unwrap();
+        assert!((second_row[0] - 0.5 * spacing).abs() < 1e-6);
+    }
+
+    #[test]
+    fn keeps_line_count_of_jittered_grid() {
+        let rectangular = Grid::new(1440, 900, &create_settings(15, GridLayout::Rectangular));
+        let jittered = Grid::new(1440, 900, &create_settings(15, GridLayout::Jittered));
+        assert_in_bounds(&jittered);
+        assert_eq!(jittered.line_count, rectangular.line_count);
+        assert_ne!(jittered.basepoints, rectangular.basepoints);
+    }
+
+    #[test]
+    fn spaces_out_poisson_disk_points() {
+        let grid = Grid::new(800, 600, &create_settings(30, GridLayout::PoissonDisk));
+        assert_in_bounds(&grid);
+
+        // Within 30% of a rectangular grid
+        let rectangular = Grid::new(800, 600, &create_settings(30, GridLayout::Rectangular));
+        let ratio = grid.line_count as f32 / rectangular.line_count as f32;
+        assert!((0.7..1.3).contains(&ratio), "ratio: {}", ratio);
+
+        let radius = POISSON_DISK_RADIUS * 30.0 / 600.0;
+        let points: Vec<(f32, f32)> = grid
+            .basepoints
+            .chunks_exact(2)
+            .map(|point| (point[0] * grid.aspect_ratio, point[1]))
+            .collect();
+        for (i, a) in points.iter().enumerate() {
+            for b in &points[i + 1..] {
+                let distance = ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+                assert!(
+                    distance >= 0.999 * radius,
+                    "{:?} and {:?} are too close",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn generates_same_random_layout_for_seed() {
+        rng::init_from_seed(&Some("grid".to_string()));
+        let a = Grid::new(800, 600, &create_settings(30, GridLayout::PoissonDisk));
+        let b = Grid::new(800, 600, &create_settings(30, GridLayout::PoissonDisk));
+        assert_eq!(a.basepoints, b.basepoints);
+    }
+
+    #[test]
+    fn thins_out_lines_with_density_map() {
+        let uniform = Grid::new(1440, 900, &create_settings(15, GridLayout::Rectangular));
+        let settings = Settings {
+            line_density: LineDensity::Radial {
+                center: 0.2,
+                edge: 1.0,
+            },
+            ..create_settings(15, GridLayout::Rectangular)
+        };
+        let grid = Grid::new(1440, 900, &settings);
+        assert!(grid.line_count < uniform.line_count);
+        assert_eq!(grid.line_weights.len(), grid.line_count as usize);
+
+        // The sparse lines in the middle are weighted up
+        let (center_weight, edge_weight) = grid
+            .basepoints
+            .chunks_exact(2)
+            .zip(&grid.line_weights)
+            .fold((0.0_f32, f32::MAX), |(center, edge), (point, &weight)| {
+                let distance = (point[0] - 0.5).abs().max((point[1] - 0.5).abs());
+                if distance < 0.1 {
+                    (center.max(weight), edge)
+                } else if distance > 0.45 {
+                    (center, edge.min(weight))
+                } else {
+                    (center, edge)
+                }
+            });
+        assert!(center_weight > 1.0);
+        assert!(edge_weight < 1.0);
+    }
+
+    #[test]
+    fn stays_under_line_budget() {
+        let settings = Settings {
+            max_lines: Some(20_000),
+            ..create_settings(15, GridLayout::Rectangular)
+        };
+        let grid = Grid::new(2560 * 3, 1440, &settings);
+        assert!(grid.line_count <= 20_000);
+        assert!(grid.line_count > 15_000);
+        assert_eq!(grid.line_count, grid.columns * grid.rows);
+
+        let settings = Settings {
+            max_lines: Some(20_000),
+            ..create_settings(15, GridLayout::Hexagonal)
+        };
+        assert!(Grid::new(2560 * 3, 1440, &settings).line_count <= 20_000);
+    }
+
+    #[test]
+    fn ignores_line_budget_when_under_it() {
+        let settings = Settings {
+            max_lines: Some(20_000),
+            ..create_settings(15, GridLayout::Rectangular)
+        };
+        assert_eq!(
+            Grid::new(1280, 800, &settings).line_count,
+            Grid::new(1280, 800, &create_settings(15, GridLayout::Rectangular)).line_count
+        );
+    }
 }
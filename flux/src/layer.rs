@@ -0,0 +1,199 @@
+// Extra sets of lines, drawn behind the main ones.
+//
+// Every layer samples the same fluid, but has its own grid and look. Farther
+// layers are dimmer and react more slowly, which gives a sense of depth.
+
+use crate::grid::{self, Grid};
+use crate::render;
+use crate::settings::{ColorMode, LineLayer, Settings};
+
+// How much dimmer and slower the farthest layer is
+const DEPTH_DIMMING: f32 = 0.7;
+const DEPTH_SLOWDOWN: f32 = 0.6;
+
+pub struct Layer {
+    layer: LineLayer,
+    grid: Grid,
+    pub lines: render::lines::Context,
+}
+
+impl Layer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target_format: wgpu::TextureFormat,
+        logical_size: wgpu::Extent3d,
+        settings: &Settings,
+        layer: &LineLayer,
+    ) -> Self {
+        let layer_settings = get_layer_settings(settings, layer);
+        let grid = Grid::new(logical_size.width, logical_size.height, &layer_settings);
+        let lines = render::lines::Context::new(
+            device,
+            queue,
+            target_format,
+            logical_size,
+            &grid,
+            &layer_settings,
+        );
+
+        Self {
+            layer: layer.clone(),
+            grid,
+            lines,
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        logical_size: wgpu::Extent3d,
+        settings: &Settings,
+    ) {
+        let layer_settings = get_layer_settings(settings, &self.layer);
+        self.lines
+            .update(device, queue, logical_size, &self.grid, &layer_settings);
+    }
+
+    pub fn update_uniforms(
+        &mut self,
+        queue: &wgpu::Queue,
+        logical_size: wgpu::Extent3d,
+        settings: &Settings,
+    ) {
+        let layer_settings = get_layer_settings(settings, &self.layer);
+        self.lines
+            .update_uniforms(queue, logical_size, &self.grid, &layer_settings);
+    }
+
+    pub fn tick(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        timestep: f32,
+        elapsed_time: f32,
+    ) {
+        let speed = 1.0 - DEPTH_SLOWDOWN * self.get_depth();
+        self.lines
+            .tick_line_uniforms(device, queue, speed * timestep, elapsed_time);
+    }
+
+    pub fn get_depth(&self) -> f32 {
+        self.layer.depth.clamp(0.0, 1.0)
+    }
+
+    /// Layers without their own palette take their colors from the main lines.
+    pub fn shares_colors(&self) -> bool {
+        self.layer.color_preset.is_none()
+    }
+}
+
+/// Create the layers, ordered from back to front.
+pub fn create_layers(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    target_format: wgpu::TextureFormat,
+    logical_size: wgpu::Extent3d,
+    settings: &Settings,
+) -> Vec<Layer> {
+    let mut layers: Vec<Layer> = settings
+        .layers
+        .iter()
+        .map(|layer| Layer::new(device, queue, target_format, logical_size, settings, layer))
+        .collect();
+    layers.sort_by(|a, b| b.get_depth().total_cmp(&a.get_depth()));
+    layers
+}
+
+/// Override the main settings with those of the layer.
+fn get_layer_settings(settings: &Settings, layer: &LineLayer) -> Settings {
+    let dimming = 1.0 - DEPTH_DIMMING * layer.depth.clamp(0.0, 1.0);
+
+    Settings {
+        grid_spacing: layer.grid_spacing,
+        line_length: layer.line_length,
+        line_width: layer.line_width,
+        view_scale: layer.view_scale,
+        color_mode: layer
+            .color_preset
+            .map(ColorMode::Preset)
+            .unwrap_or_else(|| settings.color_mode.clone()),
+        brightness_multiplier: settings.brightness_multiplier
+            * layer.opacity.clamp(0.0, 1.0)
+            * dimming,
+        max_lines: grid::get_line_budget(settings, layer.grid_spacing),
+        layers: Vec::new(),
+        ..settings.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::ColorPreset;
+
+    #[test]
+    fn dims_farther_layers() {
+        let settings = Settings::default();
+        let near = LineLayer {
+            depth: 0.0,
+            ..Default::default()
+        };
+        let far = LineLayer {
+            depth: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            get_layer_settings(&settings, &near).brightness_multiplier,
+            settings.brightness_multiplier
+        );
+        assert!(
+            get_layer_settings(&settings, &far).brightness_multiplier
+                < settings.brightness_multiplier
+        );
+    }
+
+    #[test]
+    fn overrides_palette() {
+        let settings = Settings::default();
+        let layer = LineLayer {
+            color_preset: Some(ColorPreset::Plasma),
+            ..Default::default()
+        };
+        assert_eq!(
+            get_layer_settings(&settings, &layer).color_mode,
+            ColorMode::Preset(ColorPreset::Plasma)
+        );
+        assert_eq!(
+            get_layer_settings(&settings, &LineLayer::default()).color_mode,
+            settings.color_mode
+        );
+    }
+
+    #[test]
+    fn shares_line_budget() {
+        let settings = Settings {
+            max_lines: Some(5_000),
+            layers: vec![
+                LineLayer {
+                    grid_spacing: 10,
+                    ..Default::default()
+                },
+                LineLayer {
+                    grid_spacing: 30,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let (width, height) = (2560, 1440);
+
+        let mut line_count = Grid::new(width, height, &settings).line_count;
+        for layer in settings.layers.iter() {
+            let layer_settings = get_layer_settings(&settings, layer);
+            line_count += Grid::new(width, height, &layer_settings).line_count;
+        }
+        assert!(line_count <= 5_000);
+    }
+}
@@ -1,8 +1,12 @@
+mod blue_noise;
+mod camera;
 mod flux;
 mod grid;
+mod layer;
 pub mod render;
 mod rng;
 pub mod settings;
+mod tween;
 
 pub use flux::Flux;
 pub use settings::Settings;
@@ -0,0 +1,272 @@
+use super::color::Problem;
+use crate::settings::{Background, Settings};
+
+use image::RgbaImage;
+use std::path::{Path, PathBuf};
+use wgpu::util::DeviceExt;
+
+const BLACK: image::Rgba<u8> = image::Rgba([0, 0, 0, 255]);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BackgroundUniforms {
+    // 0 => Solid
+    // 1 => Vertical gradient
+    // 2 => Radial gradient
+    // 3 => Image
+    kind: u32, // 0
+
+    // 0 => Add
+    // 1 => Screen
+    // 2 => Lighten
+    blend: u32,         // 4
+    _padding: [u32; 2], // 8
+
+    // Linear colors
+    color_a: [f32; 4], // 16
+    color_b: [f32; 4], // 32
+}
+
+impl BackgroundUniforms {
+    fn new(settings: &Settings) -> Self {
+        let black = [0.0; 3];
+        let (color_a, color_b) = match settings.background {
+            Background::Black | Background::Image(_) => (black, black),
+            Background::Solid(color) => (color, color),
+            Background::VerticalGradient { top, bottom } => (top, bottom),
+            Background::RadialGradient { center, edge } => (center, edge),
+        };
+
+        Self {
+            kind: (&settings.background).into(),
+            blend: settings.background_blend.into(),
+            _padding: [0; 2],
+            color_a: srgb_to_linear(color_a),
+            color_b: srgb_to_linear(color_b),
+        }
+    }
+}
+
+pub struct Context {
+    uniform_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    image_path: Option<PathBuf>,
+
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Context {
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, settings: &Settings) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[BackgroundUniforms::new(settings)]),
+        );
+
+        // Only reload the image when it changes
+        let image_path = match &settings.background {
+            Background::Image(path) => Some(path.clone()),
+            _ => None,
+        };
+        if image_path != self.image_path {
+            let texture_view = create_image_texture_view(
+                device,
+                queue,
+                "texture:background",
+                image_path.as_deref(),
+                BLACK,
+            );
+            self.bind_group = create_bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.uniform_buffer,
+                &self.sampler,
+                &texture_view,
+            );
+            self.image_path = image_path;
+        }
+    }
+
+    pub fn get_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn get_bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, settings: &Settings) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("buffer:BackgroundUniforms"),
+            contents: bytemuck::cast_slice(&[BackgroundUniforms::new(settings)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("sampler:background"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bind_group_layout:background"),
+            entries: &[
+                // uniforms
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // background_sampler
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // background_texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let image_path = match &settings.background {
+            Background::Image(path) => Some(path.clone()),
+            _ => None,
+        };
+        let texture_view = create_image_texture_view(
+            device,
+            queue,
+            "texture:background",
+            image_path.as_deref(),
+            BLACK,
+        );
+
+        let bind_group = create_bind_group(
+            device,
+            &bind_group_layout,
+            &uniform_buffer,
+            &sampler,
+            &texture_view,
+        );
+
+        Self {
+            uniform_buffer,
+            sampler,
+            image_path,
+
+            bind_group_layout,
+            bind_group,
+        }
+    }
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    sampler: &wgpu::Sampler,
+    texture_view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("bind_group:background"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(texture_view),
+            },
+        ],
+    })
+}
+
+/// Upload an image, or a single fallback pixel if there isn't one or it fails
+/// to load.
+pub(super) fn create_image_texture_view(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: &str,
+    path: Option<&Path>,
+    fallback: image::Rgba<u8>,
+) -> wgpu::TextureView {
+    let max_size = device.limits().max_texture_dimension_2d;
+    let image = path
+        .and_then(|path| {
+            load_image(path, max_size)
+                .map_err(|err| log::error!("Failed to load image from {}: {}", path.display(), err))
+                .ok()
+        })
+        .unwrap_or_else(|| RgbaImage::from_pixel(1, 1, fallback));
+
+    let texture = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: image.width(),
+                height: image.height(),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            // Decode to linear when sampling
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        &image,
+    );
+
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+fn load_image(path: &Path, max_size: u32) -> Result<RgbaImage, Problem> {
+    let encoded_bytes = std::fs::read(path).map_err(Problem::ReadImage)?;
+    let mut img = image::load_from_memory(&encoded_bytes).map_err(Problem::DecodeColorTexture)?;
+
+    if u32::max(img.width(), img.height()) > max_size {
+        img = img.resize(max_size, max_size, image::imageops::FilterType::Triangle);
+    }
+
+    Ok(img.to_rgba8())
+}
+
+fn srgb_to_linear(rgb: [f32; 3]) -> [f32; 4] {
+    let decode = |c: f32| {
+        let c = c.clamp(0.0, 1.0);
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    [decode(rgb[0]), decode(rgb[1]), decode(rgb[2]), 1.0]
+}
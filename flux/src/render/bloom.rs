@@ -0,0 +1,380 @@
+use super::exposure::SCENE_FORMAT;
+use crate::settings::Settings;
+
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+// The linear scene brightness at which lines start to glow. The lines are
+// drawn dim to reduce eye strain, so this sits around where a few lines
+// overlap at the default settings.
+const THRESHOLD: f32 = 0.005;
+const KNEE: f32 = 0.005;
+
+// The number of half-resolution steps in the blur chain
+const MAX_LEVELS: u32 = 6;
+const MIN_LEVEL_SIZE: u32 = 8;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomUniforms {
+    threshold: f32, // 0
+    knee: f32,      // 4
+    radius: f32,    // 8
+    intensity: f32, // 12
+}
+
+impl BloomUniforms {
+    fn new(settings: &Settings) -> Self {
+        // Threshold what will be seen, rather than the raw scene
+        let exposure = settings.exposure.max(1e-3);
+
+        Self {
+            threshold: THRESHOLD / exposure,
+            knee: KNEE / exposure,
+            radius: settings.bloom_radius.clamp(0.0, 1.0),
+            intensity: settings.bloom_intensity.max(0.0),
+        }
+    }
+}
+
+pub struct Context {
+    uniform_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+
+    // Each level is half the size of the one before it
+    level_views: Vec<wgpu::TextureView>,
+    level_bind_groups: Vec<wgpu::BindGroup>,
+    scene_bind_group: wgpu::BindGroup,
+
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+}
+
+impl Context {
+    pub fn update(&mut self, queue: &wgpu::Queue, settings: &Settings) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[BloomUniforms::new(settings)]),
+        );
+    }
+
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        size: wgpu::Extent3d,
+        scene_texture_view: &wgpu::TextureView,
+    ) {
+        self.level_views = create_level_views(device, size);
+        self.level_bind_groups = self
+            .level_views
+            .iter()
+            .map(|view| self.create_bind_group(device, view))
+            .collect();
+        self.scene_bind_group = self.create_bind_group(device, scene_texture_view);
+    }
+
+    /// Blur the bright parts of the scene and add them back on top.
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, scene_texture_view: &wgpu::TextureView) {
+        encoder.push_debug_group("bloom");
+
+        // Threshold and downsample the scene into the first level
+        run_pass(
+            encoder,
+            "flux::bloom::prefilter",
+            &self.level_views[0],
+            &self.prefilter_pipeline,
+            &self.scene_bind_group,
+            wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+        );
+
+        for level in 1..self.level_views.len() {
+            run_pass(
+                encoder,
+                "flux::bloom::downsample",
+                &self.level_views[level],
+                &self.downsample_pipeline,
+                &self.level_bind_groups[level - 1],
+                wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            );
+        }
+
+        // Walk back up the chain, accumulating the wider blurs
+        for level in (1..self.level_views.len()).rev() {
+            run_pass(
+                encoder,
+                "flux::bloom::upsample",
+                &self.level_views[level - 1],
+                &self.upsample_pipeline,
+                &self.level_bind_groups[level],
+                wgpu::LoadOp::Load,
+            );
+        }
+
+        run_pass(
+            encoder,
+            "flux::bloom::composite",
+            scene_texture_view,
+            &self.composite_pipeline,
+            &self.level_bind_groups[0],
+            wgpu::LoadOp::Load,
+        );
+
+        encoder.pop_debug_group();
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        size: wgpu::Extent3d,
+        scene_texture_view: &wgpu::TextureView,
+        settings: &Settings,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("buffer:BloomUniforms"),
+            contents: bytemuck::cast_slice(&[BloomUniforms::new(settings)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("sampler:bloom"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bind_group_layout:bloom"),
+            entries: &[
+                // uniforms
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // source_sampler
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // source_texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("pipeline_layout:bloom"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader:bloom"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../../shader/bloom.wgsl"
+            ))),
+        });
+
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::REPLACE,
+        };
+
+        let create_pipeline = |entry_point: &str, blend: Option<wgpu::BlendState>| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&format!("pipeline:bloom_{}", entry_point)),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("main_vs"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: SCENE_FORMAT,
+                        blend,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        let prefilter_pipeline = create_pipeline("prefilter", None);
+        let downsample_pipeline = create_pipeline("downsample", None);
+        let upsample_pipeline = create_pipeline("upsample", Some(additive));
+        let composite_pipeline = create_pipeline("composite", Some(additive));
+
+        let level_views = create_level_views(device, size);
+        let level_bind_groups = level_views
+            .iter()
+            .map(|view| {
+                create_bind_group(device, &bind_group_layout, &uniform_buffer, &sampler, view)
+            })
+            .collect();
+        let scene_bind_group = create_bind_group(
+            device,
+            &bind_group_layout,
+            &uniform_buffer,
+            &sampler,
+            scene_texture_view,
+        );
+
+        Self {
+            uniform_buffer,
+            sampler,
+            bind_group_layout,
+
+            level_views,
+            level_bind_groups,
+            scene_bind_group,
+
+            prefilter_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            composite_pipeline,
+        }
+    }
+
+    fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        texture_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            &self.sampler,
+            texture_view,
+        )
+    }
+}
+
+fn run_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    target_view: &wgpu::TextureView,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    load: wgpu::LoadOp<wgpu::Color>,
+) {
+    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+            depth_slice: None,
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+    rpass.set_pipeline(pipeline);
+    rpass.set_bind_group(0, bind_group, &[]);
+    rpass.draw(0..3, 0..1);
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    sampler: &wgpu::Sampler,
+    texture_view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("bind_group:bloom"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(texture_view),
+            },
+        ],
+    })
+}
+
+/// Create a view for each level of the blur chain, starting at half the size
+/// of the scene.
+fn create_level_views(device: &wgpu::Device, size: wgpu::Extent3d) -> Vec<wgpu::TextureView> {
+    let base_size = wgpu::Extent3d {
+        width: (size.width / 2).max(1),
+        height: (size.height / 2).max(1),
+        depth_or_array_layers: 1,
+    };
+
+    // Stop before the levels get too small to blur
+    let mut level_count = 1;
+    while level_count < MAX_LEVELS
+        && (base_size.width >> level_count) >= MIN_LEVEL_SIZE
+        && (base_size.height >> level_count) >= MIN_LEVEL_SIZE
+    {
+        level_count += 1;
+    }
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("texture:bloom"),
+        size: base_size,
+        mip_level_count: level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: SCENE_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+
+    (0..level_count)
+        .map(|level| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("texture_view:bloom_level"),
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
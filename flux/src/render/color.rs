@@ -1,8 +1,19 @@
-use image::{DynamicImage, GenericImage, GenericImageView, Rgba, RgbaImage};
+use crate::settings::ColorMode;
+
+use image::{
+    AnimationDecoder, DynamicImage, GenericImage, GenericImageView, ImageFormat, Rgba, RgbaImage,
+};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+// How long to show GIF frames that don't specify a delay, in seconds.
+// This matches what browsers do.
+const DEFAULT_FRAME_DELAY: f32 = 0.1;
 
 pub enum Problem {
     ReadImage(std::io::Error),
     DecodeColorTexture(image::ImageError),
+    EmptySlideshow(PathBuf),
 }
 
 impl std::fmt::Display for Problem {
@@ -12,6 +23,9 @@ impl std::fmt::Display for Problem {
             Problem::DecodeColorTexture(err) => {
                 write!(f, "Failed to decode color texture: {}", err)
             }
+            Problem::EmptySlideshow(directory) => {
+                write!(f, "No usable images found in {}", directory.display())
+            }
         }
     }
 }
@@ -36,11 +50,8 @@ impl Context {
     pub fn decode_color_texture(encoded_bytes: &[u8]) -> Result<RgbaImage, Problem> {
         log::debug!("Decoding image");
 
-        let mut img =
-            image::load_from_memory(encoded_bytes).map_err(Problem::DecodeColorTexture)?;
-        if u32::max(img.width(), img.height()) > 640 {
-            img = img.resize(640, 400, image::imageops::FilterType::Nearest);
-        }
+        let img = image::load_from_memory(encoded_bytes).map_err(Problem::DecodeColorTexture)?;
+        let img = prepare_color_image(img);
 
         log::debug!(
             "Uploading image (width: {}, height: {})",
@@ -48,8 +59,249 @@ impl Context {
             img.height()
         );
 
-        Ok(increase_black_level(&img, 25).to_rgba8())
+        Ok(img)
+    }
+}
+
+fn prepare_color_image(mut img: DynamicImage) -> RgbaImage {
+    if u32::max(img.width(), img.height()) > 640 {
+        img = img.resize(640, 400, image::imageops::FilterType::Nearest);
+    }
+
+    increase_black_level(&img, 25).to_rgba8()
+}
+
+/// A single image in an animated color source.
+#[derive(Clone)]
+pub struct Frame {
+    pub image: RgbaImage,
+    /// How long to show the frame for, in seconds.
+    pub duration: f32,
+}
+
+/// A color source that changes over time: either the frames of an animated
+/// GIF/APNG, or a slideshow of still images with crossfades.
+#[derive(Clone)]
+pub struct Animation {
+    frames: Vec<Frame>,
+    crossfade: f32,
+
+    current_frame: usize,
+    frame_time: f32,
+    needs_upload: bool,
+
+    // Scratch buffer for blending two frames during a crossfade.
+    blended_image: RgbaImage,
+}
+
+impl Animation {
+    /// Create an animation from a non-empty list of frames.
+    ///
+    /// Frames are resized to match the first frame, so that they can be
+    /// crossfaded and uploaded to the same texture.
+    pub fn new(mut frames: Vec<Frame>, crossfade: f32) -> Self {
+        assert!(!frames.is_empty(), "an animation needs at least one frame");
+
+        let (width, height) = frames[0].image.dimensions();
+        for frame in frames.iter_mut().skip(1) {
+            if frame.image.dimensions() != (width, height) {
+                frame.image = image::imageops::resize(
+                    &frame.image,
+                    width,
+                    height,
+                    image::imageops::FilterType::Triangle,
+                );
+            }
+        }
+
+        Self {
+            frames,
+            crossfade: crossfade.max(0.0),
+            current_frame: 0,
+            frame_time: 0.0,
+            needs_upload: true,
+            blended_image: RgbaImage::new(width, height),
+        }
+    }
+
+    /// Load the animation described by a color mode.
+    ///
+    /// Returns `None` for color modes that aren’t animated.
+    pub fn from_color_mode(color_mode: &ColorMode) -> Result<Option<Self>, Problem> {
+        match color_mode {
+            ColorMode::Slideshow(slideshow) => load_slideshow(
+                &slideshow.directory,
+                slideshow.interval,
+                slideshow.crossfade,
+            )
+            .map(Some),
+            ColorMode::AnimatedImageFile(path) => std::fs::read(path)
+                .map_err(Problem::ReadImage)
+                .and_then(|encoded_bytes| decode_animation(&encoded_bytes))
+                .map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.frames[0].image.dimensions()
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Advance the animation by `timestep` seconds.
+    ///
+    /// Returns the image to upload if the visible color texture has changed.
+    pub fn tick(&mut self, timestep: f32) -> Option<&RgbaImage> {
+        if self.frames.len() == 1 {
+            return std::mem::take(&mut self.needs_upload).then_some(&self.frames[0].image);
+        }
+
+        self.frame_time += timestep;
+
+        while self.frame_time >= self.frames[self.current_frame].duration {
+            self.frame_time -= self.frames[self.current_frame].duration;
+            self.current_frame = (self.current_frame + 1) % self.frames.len();
+            self.needs_upload = true;
+        }
+
+        let duration = self.frames[self.current_frame].duration;
+        let crossfade = self.crossfade.min(duration);
+        let crossfade_start = duration - crossfade;
+
+        if crossfade > 0.0 && self.frame_time > crossfade_start {
+            let next_frame = (self.current_frame + 1) % self.frames.len();
+            let blend_factor = (self.frame_time - crossfade_start) / crossfade;
+            blend_images(
+                &self.frames[self.current_frame].image,
+                &self.frames[next_frame].image,
+                blend_factor,
+                &mut self.blended_image,
+            );
+            self.needs_upload = false;
+            return Some(&self.blended_image);
+        }
+
+        std::mem::take(&mut self.needs_upload).then_some(&self.frames[self.current_frame].image)
+    }
+}
+
+fn blend_images(from: &RgbaImage, to: &RgbaImage, blend_factor: f32, out: &mut RgbaImage) {
+    let weight = (256.0 * blend_factor.clamp(0.0, 1.0)) as u16;
+    for ((out, from), to) in out.iter_mut().zip(from.iter()).zip(to.iter()) {
+        *out = ((*from as u16 * (256 - weight) + *to as u16 * weight) >> 8) as u8;
+    }
+}
+
+/// Check whether the encoded image is an animated GIF or APNG.
+pub fn is_animated(encoded_bytes: &[u8]) -> bool {
+    match image::guess_format(encoded_bytes) {
+        Ok(ImageFormat::Gif) => true,
+        Ok(ImageFormat::Png) => image::codecs::png::PngDecoder::new(Cursor::new(encoded_bytes))
+            .and_then(|decoder| decoder.is_apng())
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Decode an animated GIF or APNG into an animation.
+///
+/// Still images are decoded into a single-frame animation.
+pub fn decode_animation(encoded_bytes: &[u8]) -> Result<Animation, Problem> {
+    log::debug!("Decoding animation");
+
+    let frames = match image::guess_format(encoded_bytes) {
+        Ok(ImageFormat::Gif) => image::codecs::gif::GifDecoder::new(Cursor::new(encoded_bytes))
+            .and_then(|decoder| decoder.into_frames().collect_frames()),
+        Ok(ImageFormat::Png) if is_animated(encoded_bytes) => {
+            image::codecs::png::PngDecoder::new(Cursor::new(encoded_bytes))
+                .and_then(|decoder| decoder.apng())
+                .and_then(|decoder| decoder.into_frames().collect_frames())
+        }
+        _ => Ok(Vec::new()),
+    }
+    .map_err(Problem::DecodeColorTexture)?;
+
+    if frames.is_empty() {
+        let image = Context::decode_color_texture(encoded_bytes)?;
+        return Ok(Animation::new(
+            vec![Frame {
+                image,
+                duration: f32::INFINITY,
+            }],
+            0.0,
+        ));
     }
+
+    let frames = frames
+        .into_iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay = numer as f32 / (1000.0 * denom.max(1) as f32);
+            Frame {
+                image: prepare_color_image(DynamicImage::ImageRgba8(frame.into_buffer())),
+                duration: if delay > 0.0 {
+                    delay
+                } else {
+                    DEFAULT_FRAME_DELAY
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    log::debug!("Decoded animation with {} frames", frames.len());
+
+    Ok(Animation::new(frames, 0.0))
+}
+
+/// Load the images in a directory, in filename order, as a slideshow.
+///
+/// Images that fail to decode are skipped.
+pub fn load_slideshow(
+    directory: &Path,
+    interval: f32,
+    crossfade: f32,
+) -> Result<Animation, Problem> {
+    let mut paths = std::fs::read_dir(directory)
+        .map_err(Problem::ReadImage)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            matches!(
+                ImageFormat::from_path(path),
+                Ok(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif)
+            )
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let frames = paths
+        .iter()
+        .filter_map(|path| {
+            std::fs::read(path)
+                .map_err(Problem::ReadImage)
+                .and_then(|encoded_bytes| Context::decode_color_texture(&encoded_bytes))
+                .map_err(|err| log::warn!("Skipping {}: {}", path.display(), err))
+                .ok()
+        })
+        .map(|image| Frame {
+            image,
+            duration: interval.max(0.1),
+        })
+        .collect::<Vec<_>>();
+
+    if frames.is_empty() {
+        return Err(Problem::EmptySlideshow(directory.to_path_buf()));
+    }
+
+    log::info!(
+        "Loaded slideshow with {} images from {}",
+        frames.len(),
+        directory.display()
+    );
+
+    Ok(Animation::new(frames, crossfade))
 }
 
 fn increase_black_level(img: &DynamicImage, threshold: u8) -> DynamicImage {
@@ -78,46 +330,128 @@ fn increase_black_level(img: &DynamicImage, threshold: u8) -> DynamicImage {
     modified_img
 }
 
-pub fn load_color_texture(
-    device: &wgpu::Device,
-    queue: &wgpu::Queue,
-    img: &RgbaImage,
-) -> wgpu::TextureView {
-    let width = img.width();
-    let height = img.height();
-    let size = wgpu::Extent3d {
-        width,
-        height,
-        depth_or_array_layers: 1,
-    };
-    // Create a buffer to store the image data
-    let texture = device.create_texture(&wgpu::TextureDescriptor {
-        label: None,
-        size,
+/// An animation together with the texture its frames are uploaded to.
+pub struct AnimatedTexture {
+    animation: Animation,
+    texture: wgpu::Texture,
+}
+
+impl AnimatedTexture {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, mut animation: Animation) -> Self {
+        let (width, height) = animation.dimensions();
+        let texture = create_color_texture(device, width, height);
+
+        if let Some(img) = animation.tick(0.0) {
+            write_color_texture(queue, &texture, img);
+        }
+
+        Self { animation, texture }
+    }
+
+    pub fn create_view(&self) -> wgpu::TextureView {
+        self.texture
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    pub fn tick(&mut self, queue: &wgpu::Queue, timestep: f32) {
+        if let Some(img) = self.animation.tick(timestep) {
+            write_color_texture(queue, &self.texture, img);
+        }
+    }
+}
+
+fn create_color_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("texture:color"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
         mip_level_count: 1,
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba8Unorm,
+        // Decode to linear on sampling, so that filtering happens in linear space
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
         view_formats: &[],
         usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
-    });
+    })
+}
 
+fn write_color_texture(queue: &wgpu::Queue, texture: &wgpu::Texture, img: &RgbaImage) {
     queue.write_texture(
         wgpu::TexelCopyTextureInfo {
-            texture: &texture,
+            texture,
             mip_level: 0,
             origin: wgpu::Origin3d::ZERO,
             aspect: wgpu::TextureAspect::All,
         },
-        // TODO: fix clone()
-        &img.clone().into_raw(),
+        img.as_raw(),
         wgpu::TexelCopyBufferLayout {
             offset: 0,
-            bytes_per_row: Some(4 * width),
+            bytes_per_row: Some(4 * img.width()),
             rows_per_image: None,
         },
-        size,
+        texture.size(),
     );
+}
 
+pub fn load_color_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    img: &RgbaImage,
+) -> wgpu::TextureView {
+    let texture = create_color_texture(device, img.width(), img.height());
+    write_color_texture(queue, &texture, img);
     texture.create_view(&wgpu::TextureViewDescriptor::default())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn solid_frame(value: u8, duration: f32) -> Frame {
+        Frame {
+            image: RgbaImage::from_pixel(4, 4, Rgba([value, value, value, 255])),
+            duration,
+        }
+    }
+
+    #[test]
+    fn uploads_a_still_image_once() {
+        let mut animation = Animation::new(vec![solid_frame(100, f32::INFINITY)], 0.0);
+        assert!(animation.tick(0.0).is_some());
+        assert!(animation.tick(1.0).is_none());
+    }
+
+    #[test]
+    fn advances_frames_without_crossfade() {
+        let mut animation = Animation::new(vec![solid_frame(0, 1.0), solid_frame(200, 1.0)], 0.0);
+        assert_eq!(animation.tick(0.0).unwrap().get_pixel(0, 0)[0], 0);
+        assert!(animation.tick(0.5).is_none());
+        assert_eq!(animation.tick(0.6).unwrap().get_pixel(0, 0)[0], 200);
+        assert_eq!(animation.tick(1.0).unwrap().get_pixel(0, 0)[0], 0);
+    }
+
+    #[test]
+    fn crossfades_into_the_next_frame() {
+        let mut animation = Animation::new(vec![solid_frame(0, 10.0), solid_frame(200, 10.0)], 2.0);
+        assert_eq!(animation.tick(0.0).unwrap().get_pixel(0, 0)[0], 0);
+        assert!(animation.tick(7.0).is_none());
+        assert_eq!(animation.tick(2.0).unwrap().get_pixel(0, 0)[0], 100);
+    }
+
+    #[test]
+    fn resizes_frames_to_match_the_first() {
+        let small = Frame {
+            image: RgbaImage::new(2, 2),
+            duration: 1.0,
+        };
+        let animation = Animation::new(vec![solid_frame(0, 1.0), small], 0.0);
+        assert_eq!(animation.dimensions(), (4, 4));
+        assert!(animation
+            .frames
+            .iter()
+            .all(|frame| frame.image.dimensions() == (4, 4)));
+    }
+}
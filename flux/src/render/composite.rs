@@ -0,0 +1,340 @@
+use super::SafeZones;
+use crate::blue_noise;
+use crate::settings::{Settings, UpscaleFilter};
+
+use std::borrow::Cow;
+use std::sync::OnceLock;
+use wgpu::util::DeviceExt;
+
+// How strongly to sharpen the upscaled scene, from 0.0 to 1.0
+const SHARPNESS: f32 = 0.5;
+
+// Must match BLUE_NOISE_SIZE in composite.wgsl
+const BLUE_NOISE_SIZE: u32 = 64;
+
+// Thinner wedges than this are lost to the resolution of the scene
+const MAX_KALEIDOSCOPE_SEGMENTS: u32 = 64;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CompositeUniforms {
+    // 0 => sRGB
+    // 1 => Display P3
+    color_space: u32,   // 0
+    linear_output: u32, // 4
+
+    // The size of a step in the output format, or 0.0 to disable dithering
+    dither_scale: f32, // 8
+    film_grain: f32,   // 12
+    frame: u32,        // 16
+    // Sharpening applied when upscaling the scene
+    sharpness: f32, // 20
+    // The number of mirrored wedges, or 0 to disable the kaleidoscope
+    kaleidoscope: u32, // 24
+    // How much to darken the corners
+    vignette: f32, // 28
+    // How much to dim the lines within the safe zones
+    safe_zone_dimming: f32, // 32
+    _padding: [f32; 3],     // 36
+    // Top, bottom, left and right, as fractions of the screen
+    safe_zones: [f32; 4], // 48
+}
+
+impl CompositeUniforms {
+    fn new(settings: &Settings, swapchain_format: wgpu::TextureFormat) -> Self {
+        Self {
+            color_space: settings.color_space.into(),
+            linear_output: super::is_linear_format(swapchain_format).into(),
+            dither_scale: if settings.dither {
+                quantization_step(swapchain_format)
+            } else {
+                0.0
+            },
+            film_grain: settings.film_grain.max(0.0),
+            frame: 0,
+            sharpness: match settings.upscale_filter {
+                UpscaleFilter::Sharpen if settings.render_scale < 1.0 => SHARPNESS,
+                _ => 0.0,
+            },
+            kaleidoscope: settings
+                .kaleidoscope
+                .map_or(0, |segments| segments.clamp(1, MAX_KALEIDOSCOPE_SEGMENTS)),
+            vignette: settings.vignette.clamp(0.0, 1.0),
+            safe_zone_dimming: settings.safe_zone_dimming.clamp(0.0, 1.0),
+            _padding: [0.0; 3],
+            safe_zones: [0.0; 4],
+        }
+    }
+}
+
+/// The final pass. Exposes and tone maps the offscreen scene, then
+/// composites it over the background with the remaining screen effects.
+pub struct Context {
+    swapchain_format: wgpu::TextureFormat,
+    uniforms: CompositeUniforms,
+    uniform_buffer: wgpu::Buffer,
+
+    scene_sampler: wgpu::Sampler,
+    blue_noise_texture_view: wgpu::TextureView,
+
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Context {
+    pub fn update(&mut self, settings: &Settings) {
+        let frame = self.uniforms.frame;
+        let safe_zones = self.uniforms.safe_zones;
+        self.uniforms = CompositeUniforms::new(settings, self.swapchain_format);
+        self.uniforms.frame = frame;
+        self.uniforms.safe_zones = safe_zones;
+    }
+
+    pub fn set_safe_zones(&mut self, safe_zones: SafeZones) {
+        self.uniforms.safe_zones = [
+            safe_zones.top,
+            safe_zones.bottom,
+            safe_zones.left,
+            safe_zones.right,
+        ]
+        .map(|size| size.clamp(0.0, 0.5));
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, scene_texture_view: &wgpu::TextureView) {
+        self.bind_group = create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            &self.scene_sampler,
+            scene_texture_view,
+            &self.blue_noise_texture_view,
+        );
+    }
+
+    pub fn tick(&mut self, queue: &wgpu::Queue) {
+        // Shift the noise every frame so the pattern doesn't sit still
+        self.uniforms.frame = self.uniforms.frame.wrapping_add(1);
+
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniforms]),
+        );
+    }
+
+    /// Draw the exposed and tone mapped scene over the background to the
+    /// render target.
+    pub fn draw<'rpass>(
+        &'rpass self,
+        rpass: &mut wgpu::RenderPass<'rpass>,
+        exposure: &'rpass super::exposure::Context,
+        background: &'rpass super::background::Context,
+    ) {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.set_bind_group(1, exposure.get_bind_group(), &[]);
+        rpass.set_bind_group(2, background.get_bind_group(), &[]);
+        rpass.draw(0..3, 0..1);
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        swapchain_format: wgpu::TextureFormat,
+        exposure: &super::exposure::Context,
+        background: &super::background::Context,
+        settings: &Settings,
+    ) -> Self {
+        let uniforms = CompositeUniforms::new(settings, swapchain_format);
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("buffer:CompositeUniforms"),
+            contents: bytemuck::cast_slice(&[uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let scene_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("sampler:scene"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            ..Default::default()
+        });
+
+        // Generating the noise takes a moment, so share it between outputs
+        static BLUE_NOISE: OnceLock<Vec<u8>> = OnceLock::new();
+        let blue_noise = BLUE_NOISE.get_or_init(|| blue_noise::generate(BLUE_NOISE_SIZE));
+        let blue_noise_texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("texture:blue_noise"),
+                size: wgpu::Extent3d {
+                    width: BLUE_NOISE_SIZE,
+                    height: BLUE_NOISE_SIZE,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            blue_noise,
+        );
+        let blue_noise_texture_view =
+            blue_noise_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bind_group_layout:composite"),
+            entries: &[
+                // uniforms
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // scene_sampler
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // scene_texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // blue_noise_texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("pipeline_layout:composite"),
+            bind_group_layouts: &[
+                &bind_group_layout,
+                exposure.get_bind_group_layout(),
+                background.get_bind_group_layout(),
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader:composite"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../../shader/composite.wgsl"
+            ))),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("pipeline:composite"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("main_vs"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("main_fs"),
+                targets: &[Some(swapchain_format.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let bind_group = create_bind_group(
+            device,
+            &bind_group_layout,
+            &uniform_buffer,
+            &scene_sampler,
+            exposure.get_scene_texture_view(),
+            &blue_noise_texture_view,
+        );
+
+        Self {
+            swapchain_format,
+            uniforms,
+            uniform_buffer,
+
+            scene_sampler,
+            blue_noise_texture_view,
+
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    scene_sampler: &wgpu::Sampler,
+    scene_texture_view: &wgpu::TextureView,
+    blue_noise_texture_view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("bind_group:composite"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(scene_sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(scene_texture_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(blue_noise_texture_view),
+            },
+        ],
+    })
+}
+
+/// The size of a single step in an output format, in encoded units. Float
+/// formats have plenty of precision near black, and don't need dithering.
+fn quantization_step(format: wgpu::TextureFormat) -> f32 {
+    match format {
+        wgpu::TextureFormat::Rgb10a2Unorm => 1.0 / 1023.0,
+        wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgba32Float => 0.0,
+        _ => 1.0 / 255.0,
+    }
+}
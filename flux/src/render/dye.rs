@@ -0,0 +1,445 @@
+use crate::render::view::ViewTransform;
+use crate::settings::Settings;
+
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+// The dye is kept at a higher resolution than the fluid, so that it can hold
+// finer detail than the velocity field.
+const DYE_SCALE: u32 = 4;
+
+const DYE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DyeUniforms {
+    inverse_view_matrix: [[f32; 4]; 4], // 0
+    // sRGB colors. Unused entries are zeroed.
+    palette: [[f32; 4]; 6], // 64
+    palette_size: u32,      // 160
+    linear_output: u32,     // 164
+    delta_time: f32,        // 168
+    flow_speed: f32,        // 172
+    dissipation: f32,       // 176
+    injection: f32,         // 180
+    brightness: f32,        // 184
+    _padding: [u32; 1],     // 188
+                            // roundUp(192, 16) = 192
+}
+
+impl DyeUniforms {
+    fn new(settings: &Settings, linear_output: bool) -> Self {
+        let (palette, palette_size) = super::get_palette(&settings.color_mode);
+
+        Self {
+            inverse_view_matrix: ViewTransform::default()
+                .to_matrix()
+                .inverse()
+                .to_cols_array_2d(),
+            palette,
+            palette_size,
+            linear_output: linear_output.into(),
+            delta_time: 1.0 / 60.0,
+            flow_speed: settings.flow_speed,
+            dissipation: settings.dye_dissipation.max(0.0),
+            injection: settings.dye_injection.max(0.0),
+            brightness: settings.base_intensity * settings.brightness_multiplier,
+            _padding: [0; 1],
+        }
+    }
+}
+
+pub struct Context {
+    fluid_size: wgpu::Extent3d,
+    dye_size: wgpu::Extent3d,
+    target_format: wgpu::TextureFormat,
+    sample_count: u32,
+
+    uniforms: DyeUniforms,
+    uniform_buffer: wgpu::Buffer,
+
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
+    uniform_bind_group: wgpu::BindGroup,
+    dye_bind_group_layout: wgpu::BindGroupLayout,
+    // Ping-pong between two textures, reading from one and writing to the other
+    dye_bind_groups: [wgpu::BindGroup; 2],
+    out_dye_bind_groups: [wgpu::BindGroup; 2],
+    // The texture holding the latest dye
+    current_index: usize,
+
+    advect_pipeline: wgpu::ComputePipeline,
+    draw_pipeline: wgpu::RenderPipeline,
+}
+
+impl Context {
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, settings: &Settings) {
+        let sample_count = super::msaa::get_sample_count(settings);
+        if sample_count != self.sample_count {
+            self.sample_count = sample_count;
+            self.rebuild_draw_pipeline(device);
+        }
+
+        let linear_output = super::is_linear_format(self.target_format);
+        self.uniforms = DyeUniforms {
+            inverse_view_matrix: self.uniforms.inverse_view_matrix,
+            ..DyeUniforms::new(settings, linear_output)
+        };
+        self.write_uniforms(queue);
+    }
+
+    /// The size of the fluid this dye was made for.
+    pub fn get_fluid_size(&self) -> wgpu::Extent3d {
+        self.fluid_size
+    }
+
+    /// Rebuild the draw pipeline to render into a texture of a different format.
+    pub fn set_target_format(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target_format: wgpu::TextureFormat,
+    ) {
+        self.target_format = target_format;
+        self.rebuild_draw_pipeline(device);
+
+        self.uniforms.linear_output = super::is_linear_format(target_format).into();
+        self.write_uniforms(queue);
+    }
+
+    pub fn tick(&mut self, queue: &wgpu::Queue, timestep: f32) {
+        self.uniforms.delta_time = timestep;
+        self.write_uniforms(queue);
+    }
+
+    pub fn set_view_transform(&self, queue: &wgpu::Queue, view_transform: ViewTransform) {
+        // The view matrix comes first in the uniforms
+        let inverse_view_matrix = view_transform.to_matrix().inverse().to_cols_array_2d();
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&inverse_view_matrix),
+        );
+    }
+
+    /// Carry the dye along with the fluid, fade it, and add more where the
+    /// fluid is moving.
+    pub fn advect<'cpass>(
+        &'cpass mut self,
+        cpass: &mut wgpu::ComputePass<'cpass>,
+        velocity_bind_group: &'cpass wgpu::BindGroup,
+    ) {
+        let read_index = self.current_index;
+        let write_index = 1 - read_index;
+        self.current_index = write_index;
+
+        let (x, y) = (
+            self.dye_size.width.div_ceil(16),
+            self.dye_size.height.div_ceil(16),
+        );
+        cpass.set_pipeline(&self.advect_pipeline);
+        cpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        cpass.set_bind_group(1, &self.dye_bind_groups[read_index], &[]);
+        cpass.set_bind_group(2, &self.out_dye_bind_groups[write_index], &[]);
+        cpass.set_bind_group(3, velocity_bind_group, &[]);
+        cpass.dispatch_workgroups(x, y, 1);
+    }
+
+    pub fn draw<'rpass>(&'rpass self, rpass: &mut wgpu::RenderPass<'rpass>) {
+        rpass.set_pipeline(&self.draw_pipeline);
+        rpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        rpass.set_bind_group(1, &self.dye_bind_groups[self.current_index], &[]);
+        rpass.draw(0..3, 0..1);
+    }
+
+    fn write_uniforms(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniforms]),
+        );
+    }
+
+    fn rebuild_draw_pipeline(&mut self, device: &wgpu::Device) {
+        self.draw_pipeline = create_draw_pipeline(
+            device,
+            self.target_format,
+            self.sample_count,
+            &self.uniform_bind_group_layout,
+            &self.dye_bind_group_layout,
+        );
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        fluid_size: wgpu::Extent3d,
+        settings: &Settings,
+    ) -> Self {
+        let max_size = device.limits().max_texture_dimension_2d / DYE_SCALE;
+        let dye_size = wgpu::Extent3d {
+            width: DYE_SCALE * fluid_size.width.clamp(1, max_size),
+            height: DYE_SCALE * fluid_size.height.clamp(1, max_size),
+            depth_or_array_layers: 1,
+        };
+        let sample_count = super::msaa::get_sample_count(settings);
+        let uniforms = DyeUniforms::new(settings, super::is_linear_format(target_format));
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("buffer:DyeUniforms"),
+            contents: bytemuck::cast_slice(&[uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let linear_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("sampler:dye"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            ..Default::default()
+        });
+
+        let uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout:dye_uniforms"),
+                entries: &[
+                    // uniforms
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE
+                            | wgpu::ShaderStages::VERTEX
+                            | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // linear_sampler
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bind_group:dye_uniforms"),
+            layout: &uniform_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&linear_sampler),
+                },
+            ],
+        });
+
+        let dye_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout:dye"),
+                entries: &[
+                    // dye_texture
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let out_dye_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout:out_dye"),
+                entries: &[
+                    // out_dye_texture
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: DYE_FORMAT,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let dye_texture_views = [
+            create_dye_texture_view(device, dye_size),
+            create_dye_texture_view(device, dye_size),
+        ];
+        let create_bind_groups = |label, layout| {
+            dye_texture_views.each_ref().map(|texture_view| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some(label),
+                    layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(texture_view),
+                    }],
+                })
+            })
+        };
+        let dye_bind_groups = create_bind_groups("bind_group:dye", &dye_bind_group_layout);
+        let out_dye_bind_groups =
+            create_bind_groups("bind_group:out_dye", &out_dye_bind_group_layout);
+
+        // TODO: reuse layout from fluid
+        let velocity_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout:dye_velocity"),
+                entries: &[
+                    // velocity_texture
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    // out_velocity_texture
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: wgpu::TextureFormat::Rg32Float,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader:dye"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../../shader/dye.wgsl"))),
+        });
+
+        let advect_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("pipeline_layout:advect_dye"),
+                bind_group_layouts: &[
+                    &uniform_bind_group_layout,
+                    &dye_bind_group_layout,
+                    &out_dye_bind_group_layout,
+                    &velocity_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let advect_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("pipeline:advect_dye"),
+            layout: Some(&advect_pipeline_layout),
+            module: &shader,
+            entry_point: Some("advect"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let draw_pipeline = create_draw_pipeline(
+            device,
+            target_format,
+            sample_count,
+            &uniform_bind_group_layout,
+            &dye_bind_group_layout,
+        );
+
+        Self {
+            fluid_size,
+            dye_size,
+            target_format,
+            sample_count,
+
+            uniforms,
+            uniform_buffer,
+
+            uniform_bind_group_layout,
+            uniform_bind_group,
+            dye_bind_group_layout,
+            dye_bind_groups,
+            out_dye_bind_groups,
+            current_index: 0,
+
+            advect_pipeline,
+            draw_pipeline,
+        }
+    }
+}
+
+fn create_dye_texture_view(device: &wgpu::Device, size: wgpu::Extent3d) -> wgpu::TextureView {
+    // Starts out clear
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("texture:dye"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: DYE_FORMAT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+        view_formats: &[],
+    });
+
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+fn create_draw_pipeline(
+    device: &wgpu::Device,
+    target_format: wgpu::TextureFormat,
+    sample_count: u32,
+    uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    dye_bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("shader:draw_dye"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../../shader/dye.wgsl"))),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("pipeline_layout:draw_dye"),
+        bind_group_layouts: &[uniform_bind_group_layout, dye_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    // The dye covers the whole screen, so it replaces whatever was there
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("pipeline:draw_dye"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("main_vs"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("main_fs"),
+            targets: &[Some(target_format.into())],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    })
+}
@@ -0,0 +1,204 @@
+use crate::settings::{EndpointStyle, Settings};
+
+use std::path::{Path, PathBuf};
+use wgpu::util::DeviceExt;
+
+// The glow fades out well past the edge of a disc, so give it more room
+const GLOW_SIZE: f32 = 2.5;
+
+// Missing sprites draw nothing
+const TRANSPARENT: image::Rgba<u8> = image::Rgba([0, 0, 0, 0]);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct EndpointUniforms {
+    // 0 => Disc
+    // 1 => Glow
+    // 2 => Ring
+    // 3 => Sprite
+    style: u32,         // 0
+    size: f32,          // 4
+    opacity: f32,       // 8
+    _padding: [u32; 1], // 12
+}
+
+impl EndpointUniforms {
+    fn new(settings: &Settings) -> Self {
+        let style_size = match settings.endpoint_style {
+            EndpointStyle::Glow => GLOW_SIZE,
+            _ => 1.0,
+        };
+
+        Self {
+            style: (&settings.endpoint_style).into(),
+            size: settings.endpoint_size.max(0.0) * style_size,
+            opacity: settings.endpoint_opacity.clamp(0.0, 1.0),
+            _padding: [0; 1],
+        }
+    }
+}
+
+pub struct Context {
+    uniform_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    sprite_path: Option<PathBuf>,
+
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Context {
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, settings: &Settings) {
+        self.update_uniforms(queue, settings);
+
+        // Only reload the sprite when it changes
+        let sprite_path = get_sprite_path(settings);
+        if sprite_path != self.sprite_path {
+            let texture_view = create_sprite_texture_view(device, queue, sprite_path.as_deref());
+            self.bind_group = create_bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.uniform_buffer,
+                &self.sampler,
+                &texture_view,
+            );
+            self.sprite_path = sprite_path;
+        }
+    }
+
+    pub fn update_uniforms(&self, queue: &wgpu::Queue, settings: &Settings) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[EndpointUniforms::new(settings)]),
+        );
+    }
+
+    pub fn get_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn get_bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, settings: &Settings) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("buffer:EndpointUniforms"),
+            contents: bytemuck::cast_slice(&[EndpointUniforms::new(settings)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("sampler:endpoint_sprite"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bind_group_layout:endpoint"),
+            entries: &[
+                // uniforms
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // sprite_sampler
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // sprite_texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let sprite_path = get_sprite_path(settings);
+        let texture_view = create_sprite_texture_view(device, queue, sprite_path.as_deref());
+
+        let bind_group = create_bind_group(
+            device,
+            &bind_group_layout,
+            &uniform_buffer,
+            &sampler,
+            &texture_view,
+        );
+
+        Self {
+            uniform_buffer,
+            sampler,
+            sprite_path,
+
+            bind_group_layout,
+            bind_group,
+        }
+    }
+}
+
+fn get_sprite_path(settings: &Settings) -> Option<PathBuf> {
+    match &settings.endpoint_style {
+        EndpointStyle::Sprite(path) => Some(path.clone()),
+        _ => None,
+    }
+}
+
+fn create_sprite_texture_view(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    path: Option<&Path>,
+) -> wgpu::TextureView {
+    super::background::create_image_texture_view(
+        device,
+        queue,
+        "texture:endpoint_sprite",
+        path,
+        TRANSPARENT,
+    )
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    sampler: &wgpu::Sampler,
+    texture_view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("bind_group:endpoint"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(texture_view),
+            },
+        ],
+    })
+}
@@ -0,0 +1,460 @@
+use crate::settings::{Settings, ToneMapping};
+
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+/// The format of the offscreen texture the lines are drawn into for HDR and
+/// auto-exposure. Lines are additive, so we need headroom above 1.0 to meter
+/// and tone map them.
+pub const SCENE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// Must match the sample grid in luminance.comp.wgsl
+const SAMPLE_GRID: u32 = 256;
+const WORKGROUP_SIZE: u32 = 16;
+const BIN_COUNT: u64 = 64;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ExposureUniforms {
+    target_luminance: f32,     // 0
+    highlight_percentile: f32, // 4
+    adaptation_rate: f32,      // 8
+    min_exposure: f32,         // 12
+    max_exposure: f32,         // 16
+    delta_time: f32,           // 20
+    auto_exposure: u32,        // 24
+    exposure: f32,             // 28
+
+    // 0 => Clamp
+    // 1 => Reinhard
+    // 2 => ACES
+    tone_mapping: u32, // 32
+    headroom: f32,     // 36
+
+    _padding: [u32; 2], // 40
+}
+
+impl ExposureUniforms {
+    fn new(settings: &Settings, swapchain_format: wgpu::TextureFormat) -> Self {
+        let auto_exposure = settings.auto_exposure.clone().unwrap_or_default();
+
+        // Only float surfaces can show anything brighter than SDR white
+        let is_extended_range = matches!(
+            swapchain_format,
+            wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgba32Float
+        );
+
+        Self {
+            // Let the user brightness setting shift the target. Otherwise,
+            // metering would cancel it out.
+            target_luminance: auto_exposure.target_luminance * settings.brightness_multiplier,
+            highlight_percentile: auto_exposure.highlight_percentile.clamp(0.0, 1.0),
+            adaptation_rate: auto_exposure.adaptation_rate,
+            min_exposure: auto_exposure.min_exposure,
+            max_exposure: auto_exposure.max_exposure.max(auto_exposure.min_exposure),
+            delta_time: 1.0 / 60.0, // Initial value, will be updated every frame
+            auto_exposure: settings.auto_exposure.is_some().into(),
+            exposure: settings.exposure,
+            tone_mapping: if settings.hdr {
+                settings.tone_mapping.into()
+            } else {
+                ToneMapping::Clamp.into()
+            },
+            headroom: if settings.hdr && is_extended_range {
+                settings.hdr_headroom.max(1.0)
+            } else {
+                1.0
+            },
+            _padding: [0; 2],
+        }
+    }
+}
+
+pub struct Context {
+    swapchain_format: wgpu::TextureFormat,
+    uniforms: ExposureUniforms,
+    uniform_buffer: wgpu::Buffer,
+    histogram_buffer: wgpu::Buffer,
+    exposure_buffer: wgpu::Buffer,
+
+    scene_texture_view: wgpu::TextureView,
+
+    measure_bind_group_layout: wgpu::BindGroupLayout,
+    measure_bind_group: wgpu::BindGroup,
+    // Read by the composite pass to expose and tone map the scene
+    apply_bind_group_layout: wgpu::BindGroupLayout,
+    apply_bind_group: wgpu::BindGroup,
+
+    measure_pipeline: wgpu::ComputePipeline,
+    adapt_pipeline: wgpu::ComputePipeline,
+}
+
+impl Context {
+    pub fn update(&mut self, settings: &Settings) {
+        let delta_time = self.uniforms.delta_time;
+        self.uniforms = ExposureUniforms::new(settings, self.swapchain_format);
+        self.uniforms.delta_time = delta_time;
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) {
+        self.scene_texture_view = create_scene_texture_view(device, size);
+        self.measure_bind_group = create_measure_bind_group(
+            device,
+            &self.measure_bind_group_layout,
+            &self.uniform_buffer,
+            &self.histogram_buffer,
+            &self.exposure_buffer,
+            &self.scene_texture_view,
+        );
+    }
+
+    pub fn tick(&mut self, queue: &wgpu::Queue, timestep: f32) {
+        self.uniforms.delta_time = timestep;
+
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniforms]),
+        );
+    }
+
+    pub fn has_auto_exposure(&self) -> bool {
+        self.uniforms.auto_exposure == 1
+    }
+
+    pub fn get_scene_texture_view(&self) -> &wgpu::TextureView {
+        &self.scene_texture_view
+    }
+
+    pub fn get_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.apply_bind_group_layout
+    }
+
+    pub fn get_bind_group(&self) -> &wgpu::BindGroup {
+        &self.apply_bind_group
+    }
+
+    /// Meter the scene and adapt the exposure towards the target luminance.
+    pub fn measure<'cpass>(&'cpass self, cpass: &mut wgpu::ComputePass<'cpass>) {
+        let workgroup_count = SAMPLE_GRID / WORKGROUP_SIZE;
+        cpass.set_bind_group(0, &self.measure_bind_group, &[]);
+        cpass.set_pipeline(&self.measure_pipeline);
+        cpass.dispatch_workgroups(workgroup_count, workgroup_count, 1);
+        cpass.set_pipeline(&self.adapt_pipeline);
+        cpass.dispatch_workgroups(1, 1, 1);
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        swapchain_format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+        settings: &Settings,
+    ) -> Self {
+        let uniforms = ExposureUniforms::new(settings, swapchain_format);
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("buffer:ExposureUniforms"),
+            contents: bytemuck::cast_slice(&[uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let histogram_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("buffer:luminance_histogram"),
+            size: 4 * BIN_COUNT,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        // Exposure, average luminance, highlight luminance
+        let exposure_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("buffer:exposure"),
+            contents: bytemuck::cast_slice(&[1.0f32, 0.0, 0.0]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+
+        let scene_texture_view = create_scene_texture_view(device, size);
+
+        let measure_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout:measure_exposure"),
+                entries: &[
+                    // uniforms
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // histogram
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // exposure
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // scene_texture
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let apply_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout:apply_exposure"),
+                entries: &[
+                    // uniforms
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // exposure
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let luminance_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader:luminance"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../../shader/luminance.comp.wgsl"
+            ))),
+        });
+
+        let measure_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("pipeline_layout:measure_exposure"),
+                bind_group_layouts: &[&measure_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let measure_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("pipeline:measure_luminance"),
+            layout: Some(&measure_pipeline_layout),
+            module: &luminance_shader,
+            entry_point: Some("measure"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let adapt_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("pipeline:adapt_exposure"),
+            layout: Some(&measure_pipeline_layout),
+            module: &luminance_shader,
+            entry_point: Some("adapt"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let measure_bind_group = create_measure_bind_group(
+            device,
+            &measure_bind_group_layout,
+            &uniform_buffer,
+            &histogram_buffer,
+            &exposure_buffer,
+            &scene_texture_view,
+        );
+        let apply_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bind_group:apply_exposure"),
+            layout: &apply_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: exposure_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            swapchain_format,
+            uniforms,
+            uniform_buffer,
+            histogram_buffer,
+            exposure_buffer,
+
+            scene_texture_view,
+
+            measure_bind_group_layout,
+            measure_bind_group,
+            apply_bind_group_layout,
+            apply_bind_group,
+
+            measure_pipeline,
+            adapt_pipeline,
+        }
+    }
+}
+
+fn create_measure_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    histogram_buffer: &wgpu::Buffer,
+    exposure_buffer: &wgpu::Buffer,
+    scene_texture_view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("bind_group:measure_exposure"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: histogram_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: exposure_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(scene_texture_view),
+            },
+        ],
+    })
+}
+
+fn create_scene_texture_view(device: &wgpu::Device, size: wgpu::Extent3d) -> wgpu::TextureView {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("texture:scene"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: SCENE_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::AutoExposure;
+
+    // Fill the scene with a flat gray of the given luminance, meter it and
+    // read back the exposure, average and highlight luminance
+    fn measure_flat_scene(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        context: &Context,
+        luminance: f64,
+    ) -> [f32; 3] {
+        let mut encoder = device.create_command_encoder(&Default::default());
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: context.get_scene_texture_view(),
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: luminance,
+                        g: luminance,
+                        b: luminance,
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        context.measure(&mut encoder.begin_compute_pass(&Default::default()));
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 12,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_buffer_to_buffer(&context.exposure_buffer, 0, &readback_buffer, 0, 12);
+        queue.submit(Some(encoder.finish()));
+
+        readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+        let values = bytemuck::cast_slice(&readback_buffer.slice(..).get_mapped_range()).to_vec();
+        [values[0], values[1], values[2]]
+    }
+
+    #[test]
+    fn meters_dim_scenes() {
+        let Some((device, queue)) = super::super::request_test_device() else {
+            eprintln!("Skipping: no GPU");
+            return;
+        };
+
+        let settings = Settings {
+            auto_exposure: Some(AutoExposure {
+                highlight_percentile: 1.0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let size = wgpu::Extent3d {
+            width: 64,
+            height: 64,
+            depth_or_array_layers: 1,
+        };
+        let mut context = Context::new(&device, SCENE_FORMAT, size, &settings);
+        context.tick(&queue, 1.0 / 60.0);
+
+        // Around the default target, below what a fixed-point sum would resolve
+        let [exposure, average_luminance, _] = measure_flat_scene(&device, &queue, &context, 0.003);
+        assert!(
+            (average_luminance / 0.003 - 1.0).abs() < 0.1,
+            "metered {average_luminance}"
+        );
+        // A scene darker than the target is brightened
+        assert!(exposure > 1.0, "exposure {exposure}");
+    }
+}
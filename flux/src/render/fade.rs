@@ -0,0 +1,336 @@
+use crate::settings::Settings;
+
+use std::borrow::Cow;
+
+pub struct Context {
+    target_format: wgpu::TextureFormat,
+    duration: f32,
+    // Seconds since the fade started
+    elapsed: f32,
+    // Whether to keep the next frame drawn to the target
+    snapshot_pending: bool,
+
+    sampler: wgpu::Sampler,
+    snapshot_texture: wgpu::Texture,
+    snapshot_texture_view: wgpu::TextureView,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Context {
+    pub fn update(&mut self, settings: &Settings) {
+        self.duration = settings.fade_duration.max(0.0);
+    }
+
+    /// Present to a target of a different format. Any fade in progress is
+    /// stopped.
+    pub fn set_target_format(&mut self, device: &wgpu::Device, target_format: wgpu::TextureFormat) {
+        self.target_format = target_format;
+        self.set_snapshot_texture(
+            device,
+            create_texture(device, target_format, self.snapshot_texture.size()),
+        );
+        self.pipeline = create_pipeline(device, &self.bind_group_layout, target_format);
+        self.elapsed = self.duration;
+    }
+
+    /// Keep the next frame drawn to the target and fade from it to the frames
+    /// that follow.
+    pub fn request_snapshot(&mut self) {
+        self.snapshot_pending = true;
+    }
+
+    pub fn is_snapshot_pending(&self) -> bool {
+        self.snapshot_pending
+    }
+
+    /// Copy the frame just drawn to the target, if a snapshot was requested,
+    /// and start fading from it. No fade starts if fades are disabled, or if
+    /// the target can't be copied from.
+    pub fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::Texture,
+    ) {
+        if !self.snapshot_pending {
+            return;
+        }
+        self.snapshot_pending = false;
+
+        let can_copy = target.usage().contains(wgpu::TextureUsages::COPY_SRC)
+            && target.format().remove_srgb_suffix() == self.target_format.remove_srgb_suffix();
+        if !self.is_enabled() || !can_copy {
+            return;
+        }
+
+        if self.snapshot_texture.size() != target.size() {
+            self.set_snapshot_texture(
+                device,
+                create_texture(device, self.target_format, target.size()),
+            );
+        }
+        encoder.copy_texture_to_texture(
+            target.as_image_copy(),
+            self.snapshot_texture.as_image_copy(),
+            target.size(),
+        );
+        self.elapsed = 0.0;
+    }
+
+    fn set_snapshot_texture(&mut self, device: &wgpu::Device, snapshot_texture: wgpu::Texture) {
+        self.snapshot_texture_view =
+            snapshot_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.snapshot_texture = snapshot_texture;
+        self.bind_group = create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.sampler,
+            &self.snapshot_texture_view,
+        );
+    }
+
+    pub fn tick(&mut self, timestep: f32) {
+        self.elapsed = (self.elapsed + timestep).min(self.duration);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.duration > 0.0
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.elapsed < self.duration
+    }
+
+    /// Mix the snapshot over the frame, easing it out over the duration.
+    pub fn draw<'rpass>(&'rpass self, rpass: &mut wgpu::RenderPass<'rpass>) {
+        let progress = (self.elapsed / self.duration).clamp(0.0, 1.0);
+        let opacity = (1.0 - progress * progress * (3.0 - 2.0 * progress)) as f64;
+
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.set_blend_constant(wgpu::Color {
+            r: opacity,
+            g: opacity,
+            b: opacity,
+            a: opacity,
+        });
+        rpass.draw(0..3, 0..1);
+    }
+
+    /// The snapshot starts out black, so the first frames fade in.
+    pub fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+        settings: &Settings,
+    ) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("sampler:fade"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let snapshot_texture = create_texture(device, target_format, size);
+        let snapshot_texture_view =
+            snapshot_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bind_group_layout:fade"),
+            entries: &[
+                // snapshot_sampler
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+                // snapshot_texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group =
+            create_bind_group(device, &bind_group_layout, &sampler, &snapshot_texture_view);
+
+        let pipeline = create_pipeline(device, &bind_group_layout, target_format);
+
+        Self {
+            target_format,
+            duration: settings.fade_duration.max(0.0),
+            elapsed: 0.0,
+            snapshot_pending: false,
+
+            sampler,
+            snapshot_texture,
+            snapshot_texture_view,
+            bind_group_layout,
+            bind_group,
+
+            pipeline,
+        }
+    }
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    target_format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("shader:fade"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../../shader/fade.wgsl"))),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("pipeline_layout:fade"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    // src * opacity + dst * (1 - opacity)
+    let blend_component = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Constant,
+        dst_factor: wgpu::BlendFactor::OneMinusConstant,
+        operation: wgpu::BlendOperation::Add,
+    };
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("pipeline:fade"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("main_vs"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("main_fs"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: target_format,
+                blend: Some(wgpu::BlendState {
+                    color: blend_component,
+                    alpha: blend_component,
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    snapshot_texture_view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("bind_group:fade"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(snapshot_texture_view),
+            },
+        ],
+    })
+}
+
+fn create_texture(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    size: wgpu::Extent3d,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("texture:fade"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fades_from_requested_snapshots_only() {
+        let Some((device, queue)) = super::super::request_test_device() else {
+            eprintln!("Skipping: no GPU");
+            return;
+        };
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let size = wgpu::Extent3d {
+            width: 64,
+            height: 64,
+            depth_or_array_layers: 1,
+        };
+        let create_target = |usage: wgpu::TextureUsages| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | usage,
+                view_formats: &[],
+            })
+        };
+        let target = create_target(wgpu::TextureUsages::COPY_SRC);
+
+        let mut fade = Context::new(&device, format, size, &Settings::default());
+        fade.tick(fade.duration);
+        assert!(!fade.is_active());
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        fade.capture(&device, &mut encoder, &target);
+        assert!(!fade.is_active());
+
+        // A target that can't be copied from has nothing to fade from
+        fade.request_snapshot();
+        fade.capture(
+            &device,
+            &mut encoder,
+            &create_target(wgpu::TextureUsages::empty()),
+        );
+        assert!(!fade.is_snapshot_pending());
+        assert!(!fade.is_active());
+
+        fade.request_snapshot();
+        fade.capture(&device, &mut encoder, &target);
+        assert!(!fade.is_snapshot_pending());
+        assert!(fade.is_active());
+        queue.submit(Some(encoder.finish()));
+    }
+}
@@ -1,11 +1,14 @@
 use crate::grid::Grid;
 use crate::render::view::ViewTransform;
-use crate::settings::{ColorMode, Settings};
+use crate::settings::{BlendMode, ColorMode, ColorPreset, Settings};
 
 use bytemuck::Zeroable;
 use std::borrow::Cow;
 use wgpu::util::DeviceExt;
 
+// Enough to draw a smooth curve. Each segment adds two triangles per line.
+const MAX_LINE_SEGMENTS: u32 = 16;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct LineUniforms {
@@ -23,7 +26,7 @@ struct LineUniforms {
     // 0 => The "Original" color preset
     // 1 => A color preset with a color wheel
     // 2 => Sample colors from a texture
-    // 3 => Sample colors from a texture with SRGB (unsupported)
+    // 3 => Sample colors from a texture with SRGB
     color_mode: u32, // 44
 
     delta_time: f32, // 48
@@ -31,11 +34,49 @@ struct LineUniforms {
     // Scale factor to normalize brightness across different line counts
     // Higher line counts need lower brightness to avoid additive accumulation
     brightness_scale: f32, // 52
-                           // roundUp(56, 8) = 56
+
+    // Caps on line color, in HSL, to keep the wallpaper easy on the eyes
+    max_saturation: f32, // 56
+    max_lightness: f32,  // 60
+    base_intensity: f32, // 64
+
+    // 1 if the render target expects linear colors. Line colors are
+    // sRGB-encoded throughout, and the final fragment color is decoded once
+    // before output.
+    linear_output: u32, // 68
+
+    // How far segmented lines bend, and how much they narrow towards the base
+    line_curvature: f32, // 72
+    line_taper: f32,     // 76
+
+    // 0 => Palette
+    // 1 => Flow direction
+    // 2 => Flow speed
+    color_mapping: u32, // 80
+    _padding: [u32; 1], // 84
+                        // roundUp(88, 8) = 88
 }
 
 impl LineUniforms {
-    fn new(screen_size: wgpu::Extent3d, grid: &Grid, settings: &Settings) -> Self {
+    fn new(
+        screen_size: wgpu::Extent3d,
+        grid: &Grid,
+        settings: &Settings,
+        linear_output: bool,
+    ) -> Self {
+        let line_uniforms = Self::from_settings(screen_size, grid, settings, linear_output);
+        let brightness_scale = line_uniforms.brightness_scale;
+        log::info!("Display brightness_scale: {} (line_count: {}, user_multiplier: {})", brightness_scale, grid.line_count, settings.brightness_multiplier);
+        line_uniforms
+    }
+
+    // Like `new`, but without logging, for settings that change every frame
+    fn from_settings(
+        screen_size: wgpu::Extent3d,
+        grid: &Grid,
+        settings: &Settings,
+        linear_output: bool,
+    ) -> Self {
         // TODO: can we compute the scale factor from the grid?
         let line_scale_factor =
             get_line_scale_factor(screen_size.width as f32, screen_size.height as f32);
@@ -44,11 +85,15 @@ impl LineUniforms {
         // Base of 12000 lines gets scale 1.0 - more aggressive scaling
         // More lines = much lower scale to prevent additive brightness accumulation
         let base_line_count = 12000.0_f32;
-        let line_ratio = base_line_count / grid.line_count as f32;
+        // With auto-exposure, brightness is metered from the rendered lines instead
+        let line_ratio = if settings.auto_exposure.is_some() {
+            1.0
+        } else {
+            base_line_count / grid.line_count as f32
+        };
         // Use linear ratio (not sqrt) for more aggressive darkening on high-line-count displays
         // Apply user brightness multiplier
         let brightness_scale = line_ratio.min(1.0) * settings.brightness_multiplier;
-        log::info!("Display brightness_scale: {} (line_count: {}, user_multiplier: {})", brightness_scale, grid.line_count, settings.brightness_multiplier);
 
         Self {
             aspect: grid.aspect_ratio,
@@ -64,6 +109,14 @@ impl LineUniforms {
             color_mode: settings.color_mode.clone().into(),
             delta_time: 1.0 / 60.0, // Initial value, will be updated every frame
             brightness_scale,
+            max_saturation: settings.max_saturation,
+            max_lightness: settings.max_lightness,
+            base_intensity: settings.base_intensity,
+            linear_output: linear_output.into(),
+            line_curvature: get_line_curvature(settings),
+            line_taper: settings.line_taper.clamp(0.0, 1.0),
+            color_mapping: settings.color_mapping.into(),
+            _padding: [0; 1],
         }
     }
 
@@ -127,9 +180,11 @@ pub struct Context {
     work_group_count: u32,
     frame_num: usize,
 
+    line_segments: u32,
     line_vertex_buffer: wgpu::Buffer,
     endpoint_vertex_buffer: wgpu::Buffer,
     basepoints_buffer: wgpu::Buffer,
+    line_weights_buffer: wgpu::Buffer,
     view_uniform_buffer: wgpu::Buffer,
     line_uniforms: LineUniforms,
     line_uniform_buffer: wgpu::Buffer,
@@ -144,11 +199,18 @@ pub struct Context {
     line_bind_groups: Vec<wgpu::BindGroup>,
 
     pub color_mode: u32,
+    linear_output: bool,
+    target_format: wgpu::TextureFormat,
+    blend_mode: BlendMode,
+    sample_count: u32,
     color_texture_sampler: wgpu::Sampler,
     color_texture_view: wgpu::TextureView,
     color_buffer: wgpu::Buffer,
+    // The preset whose color wheel is in `color_buffer`
+    color_wheel_preset: Option<ColorPreset>,
     color_bind_group_layout: wgpu::BindGroupLayout,
     color_bind_group: wgpu::BindGroup,
+    endpoint: super::endpoint::Context,
 
     place_lines_pipeline: wgpu::ComputePipeline,
     draw_line_pipeline: wgpu::RenderPipeline,
@@ -165,16 +227,46 @@ impl Context {
         settings: &Settings,
     ) {
         self.line_uniforms = {
-            let mut new_line_uniforms = LineUniforms::new(screen_size, grid, settings);
+            let mut new_line_uniforms =
+                LineUniforms::new(screen_size, grid, settings, self.linear_output);
             new_line_uniforms.line_noise_offset_1 = self.line_uniforms.line_noise_offset_1;
             new_line_uniforms.line_noise_offset_2 = self.line_uniforms.line_noise_offset_2;
             new_line_uniforms.line_noise_blend_factor = self.line_uniforms.line_noise_blend_factor;
 
+            // Keep sampling the current color texture with the right encoding
+            if !matches!(settings.color_mode, ColorMode::Preset(_))
+                && matches!(self.color_mode, 2 | 3)
+            {
+                new_line_uniforms.color_mode = self.color_mode;
+            }
+
             new_line_uniforms
         };
 
+        self.endpoint.update(device, queue, settings);
+
+        let line_segments = settings.line_segments.clamp(1, MAX_LINE_SEGMENTS);
+        if line_segments != self.line_segments {
+            self.line_segments = line_segments;
+            self.line_vertex_buffer = create_line_vertex_buffer(device, line_segments);
+        }
+
+        let sample_count = super::msaa::get_sample_count(settings);
+        if settings.blend_mode != self.blend_mode || sample_count != self.sample_count {
+            self.blend_mode = settings.blend_mode;
+            self.sample_count = sample_count;
+            self.rebuild_draw_pipelines(device);
+        }
+
         if let ColorMode::Preset(preset) = settings.color_mode {
-            if let Some(color_wheel) = preset.to_color_wheel() {
+            // Only rebuild the color wheel when the preset changes
+            let color_wheel = if self.color_wheel_preset != Some(preset) {
+                preset.to_color_wheel()
+            } else {
+                None
+            };
+
+            if let Some(color_wheel) = color_wheel {
                 self.color_buffer = device.create_buffer(&wgpu::BufferDescriptor {
                     label: Some("buffer:color"),
                     size: 4 * (color_wheel.len() as u64),
@@ -186,6 +278,7 @@ impl Context {
 
                 self.color_mode = 1;
                 self.update_color_bindings(device, queue, None, None);
+                self.color_wheel_preset = Some(preset);
             }
         }
 
@@ -196,6 +289,33 @@ impl Context {
         );
     }
 
+    /// Rewrite the uniforms for numeric settings that are part way through a
+    /// tween. Unlike `update`, this keeps the colors, buffers and pipelines.
+    pub fn update_uniforms(
+        &mut self,
+        queue: &wgpu::Queue,
+        screen_size: wgpu::Extent3d,
+        grid: &Grid,
+        settings: &Settings,
+    ) {
+        self.line_uniforms = LineUniforms {
+            line_noise_offset_1: self.line_uniforms.line_noise_offset_1,
+            line_noise_offset_2: self.line_uniforms.line_noise_offset_2,
+            line_noise_blend_factor: self.line_uniforms.line_noise_blend_factor,
+            color_mode: self.line_uniforms.color_mode,
+            delta_time: self.line_uniforms.delta_time,
+            ..LineUniforms::from_settings(screen_size, grid, settings, self.linear_output)
+        };
+
+        self.endpoint.update_uniforms(queue, settings);
+
+        queue.write_buffer(
+            &self.line_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.line_uniforms]),
+        );
+    }
+
     pub fn set_view_transform(&self, queue: &wgpu::Queue, view_transform: ViewTransform) {
         let view_matrix = ViewUniform::from(view_transform);
         queue.write_buffer(
@@ -213,12 +333,18 @@ impl Context {
         some_color_buffer: Option<wgpu::Buffer>,
     ) {
         if let Some(color_texture_view) = some_color_texture_view {
+            self.color_mode = if color_texture_view.texture().format().is_srgb() {
+                3
+            } else {
+                2
+            };
             self.color_texture_view = color_texture_view;
-            self.color_mode = 2;
+            self.color_wheel_preset = None;
         }
         if let Some(color_buffer) = some_color_buffer {
             self.color_buffer = color_buffer;
             self.color_mode = 1;
+            self.color_wheel_preset = None;
         }
 
         self.color_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -259,6 +385,44 @@ impl Context {
         );
     }
 
+    /// Rebuild the draw pipelines to render into a texture of a different format.
+    pub fn set_target_format(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target_format: wgpu::TextureFormat,
+    ) {
+        self.target_format = target_format;
+        self.rebuild_draw_pipelines(device);
+
+        self.linear_output = super::is_linear_format(target_format);
+        self.line_uniforms.linear_output = self.linear_output.into();
+
+        queue.write_buffer(
+            &self.line_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.line_uniforms]),
+        );
+    }
+
+    fn rebuild_draw_pipelines(&mut self, device: &wgpu::Device) {
+        (self.draw_line_pipeline, self.draw_endpoint_pipeline) = create_draw_pipelines(
+            device,
+            self.target_format,
+            self.blend_mode,
+            self.sample_count,
+            &self.uniform_bind_group_layout,
+            &self.view_uniform_bind_group_layout,
+            self.endpoint.get_bind_group_layout(),
+        );
+    }
+
+    /// The color mode the lines are drawn with.
+    #[cfg(test)]
+    pub fn get_line_color_mode(&self) -> u32 {
+        self.line_uniforms.color_mode
+    }
+
     pub fn update_line_color_mode(&mut self, _device: &wgpu::Device, queue: &wgpu::Queue) {
         self.line_uniforms.color_mode = self.color_mode;
 
@@ -289,6 +453,12 @@ impl Context {
                 | wgpu::BufferUsages::COPY_DST,
         });
 
+        let line_weights_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("buffer:line_weights"),
+            contents: bytemuck::cast_slice(&grid.line_weights),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
         let lines = vec![Line::zeroed(); grid.line_count as usize];
 
         let line_buffers = (0..2)
@@ -342,6 +512,11 @@ impl Context {
                     binding: 1,
                     resource: basepoints_buffer.as_entire_binding(),
                 },
+                // line_weights
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: line_weights_buffer.as_entire_binding(),
+                },
                 // linear_sampler
                 wgpu::BindGroupEntry {
                     binding: 2,
@@ -360,21 +535,19 @@ impl Context {
         self.line_buffers = line_buffers;
         self.line_bind_groups = line_bind_groups;
         self.basepoints_buffer = basepoints_buffer;
+        self.line_weights_buffer = line_weights_buffer;
     }
 
     pub fn new(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        swapchain_format: wgpu::TextureFormat,
+        target_format: wgpu::TextureFormat,
         screen_size: wgpu::Extent3d,
         grid: &Grid,
         settings: &Settings,
     ) -> Self {
-        let line_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("buffer:vertices"),
-            contents: bytemuck::cast_slice(&LINE_VERTICES),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+        let line_segments = settings.line_segments.clamp(1, MAX_LINE_SEGMENTS);
+        let line_vertex_buffer = create_line_vertex_buffer(device, line_segments);
 
         let endpoint_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("buffer:endpoints"),
@@ -382,7 +555,8 @@ impl Context {
             usage: wgpu::BufferUsages::VERTEX,
         });
 
-        let line_uniforms = LineUniforms::new(screen_size, grid, settings);
+        let linear_output = super::is_linear_format(target_format);
+        let line_uniforms = LineUniforms::new(screen_size, grid, settings, linear_output);
 
         let line_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("buffer:LineUniforms"),
@@ -404,6 +578,12 @@ impl Context {
                 | wgpu::BufferUsages::COPY_DST,
         });
 
+        let line_weights_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("buffer:line_weights"),
+            contents: bytemuck::cast_slice(&grid.line_weights),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
         let lines = vec![Line::zeroed(); grid.line_count as usize];
 
         let line_buffers = (0..2)
@@ -478,6 +658,17 @@ impl Context {
                         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                         count: None,
                     },
+                    // line_weights
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
             });
 
@@ -499,6 +690,11 @@ impl Context {
                     binding: 1,
                     resource: basepoints_buffer.as_entire_binding(),
                 },
+                // line_weights
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: line_weights_buffer.as_entire_binding(),
+                },
                 // linear_sampler
                 wgpu::BindGroupEntry {
                     binding: 2,
@@ -721,113 +917,17 @@ impl Context {
                 cache: None,
             });
 
-        let draw_line_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("pipeline_layout:draw_line"),
-                bind_group_layouts: &[&uniform_bind_group_layout, &view_uniform_bind_group_layout],
-                push_constant_ranges: &[],
-            });
-
-        let draw_line_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("shader:draw_line"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../../shader/line.wgsl"))),
-        });
+        let endpoint = super::endpoint::Context::new(device, queue, settings);
 
-        let vertex_buffer_layouts = [
-            wgpu::VertexBufferLayout {
-                array_stride: std::mem::size_of::<Line>() as wgpu::BufferAddress,
-                step_mode: wgpu::VertexStepMode::Instance,
-                attributes: &wgpu::vertex_attr_array![
-                            0 => Float32x2, 1 => Float32x2, 2 => Float32x4, 3 => Float32x3, 4 => Float32],
-            },
-            wgpu::VertexBufferLayout {
-                array_stride: 2 * std::mem::size_of::<f32>() as wgpu::BufferAddress,
-                step_mode: wgpu::VertexStepMode::Instance,
-                attributes: &wgpu::vertex_attr_array![5 => Float32x2],
-            },
-            wgpu::VertexBufferLayout {
-                array_stride: 2 * std::mem::size_of::<f32>() as wgpu::BufferAddress,
-                step_mode: wgpu::VertexStepMode::Vertex,
-                attributes: &wgpu::vertex_attr_array![6 => Float32x2],
-            },
-        ];
-
-        let color_targets = [Some(wgpu::ColorTargetState {
-            format: swapchain_format,
-            blend: Some(wgpu::BlendState {
-                color: wgpu::BlendComponent {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::One,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                alpha: wgpu::BlendComponent {
-                    src_factor: wgpu::BlendFactor::One,
-                    dst_factor: wgpu::BlendFactor::One,
-                    operation: wgpu::BlendOperation::Add,
-                },
-            }),
-            write_mask: wgpu::ColorWrites::ALL,
-        })];
-
-        let draw_line_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("pipeline:draw_line"),
-            layout: Some(&draw_line_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &draw_line_shader,
-                entry_point: Some("main_vs"),
-                buffers: &vertex_buffer_layouts,
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &draw_line_shader,
-                entry_point: Some("main_fs"),
-                targets: &color_targets,
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-            cache: None,
-        });
-
-        let draw_endpoint_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("pipeline_layout:draw_endpoint"),
-                bind_group_layouts: &[&uniform_bind_group_layout, &view_uniform_bind_group_layout],
-                push_constant_ranges: &[],
-            });
-
-        let draw_endpoint_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("shader:draw_endpoint"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
-                "../../shader/endpoint.wgsl"
-            ))),
-        });
-
-        // TODO: reuse draw_line layout
-        let draw_endpoint_pipeline =
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("pipeline:draw_endpoint"),
-                layout: Some(&draw_endpoint_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &draw_endpoint_shader,
-                    entry_point: Some("main_vs"),
-                    buffers: &vertex_buffer_layouts,
-                    compilation_options: Default::default(),
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &draw_endpoint_shader,
-                    entry_point: Some("main_fs"),
-                    targets: &color_targets,
-                    compilation_options: Default::default(),
-                }),
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-                cache: None,
-            });
+        let (draw_line_pipeline, draw_endpoint_pipeline) = create_draw_pipelines(
+            device,
+            target_format,
+            settings.blend_mode,
+            super::msaa::get_sample_count(settings),
+            &uniform_bind_group_layout,
+            &view_uniform_bind_group_layout,
+            endpoint.get_bind_group_layout(),
+        );
 
         let work_group_count = ((grid.line_count as f32) / 64.0).ceil() as u32;
 
@@ -836,9 +936,11 @@ impl Context {
             work_group_count,
             frame_num: 0,
 
+            line_segments,
             line_vertex_buffer,
             endpoint_vertex_buffer,
             basepoints_buffer,
+            line_weights_buffer,
             view_uniform_buffer,
             line_uniforms,
             line_uniform_buffer,
@@ -854,10 +956,16 @@ impl Context {
             line_bind_groups,
 
             color_mode: line_uniforms.color_mode,
+            linear_output,
+            target_format,
+            blend_mode: settings.blend_mode,
+            sample_count: super::msaa::get_sample_count(settings),
             color_texture_view,
             color_buffer,
+            color_wheel_preset: None,
             color_bind_group_layout,
             color_bind_group,
+            endpoint,
 
             place_lines_pipeline,
             draw_line_pipeline,
@@ -892,13 +1000,14 @@ impl Context {
         rpass.set_vertex_buffer(0, self.line_buffers[self.frame_num].slice(..));
         rpass.set_vertex_buffer(1, self.basepoints_buffer.slice(..));
         rpass.set_vertex_buffer(2, self.line_vertex_buffer.slice(..));
-        rpass.draw(0..6, 0..self.line_count);
+        rpass.draw(0..6 * self.line_segments, 0..self.line_count);
     }
 
     pub fn draw_endpoints<'rpass>(&'rpass self, rpass: &mut wgpu::RenderPass<'rpass>) {
         rpass.set_pipeline(&self.draw_endpoint_pipeline);
         rpass.set_bind_group(0, &self.uniform_bind_group, &[]);
         rpass.set_bind_group(1, &self.view_uniform_bind_group, &[]);
+        rpass.set_bind_group(2, self.endpoint.get_bind_group(), &[]);
         rpass.set_vertex_buffer(0, self.line_buffers[self.frame_num].slice(..));
         rpass.set_vertex_buffer(1, self.basepoints_buffer.slice(..));
         rpass.set_vertex_buffer(2, self.endpoint_vertex_buffer.slice(..));
@@ -906,12 +1015,179 @@ impl Context {
     }
 }
 
+fn create_draw_pipelines(
+    device: &wgpu::Device,
+    target_format: wgpu::TextureFormat,
+    blend_mode: BlendMode,
+    sample_count: u32,
+    uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    view_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    endpoint_bind_group_layout: &wgpu::BindGroupLayout,
+) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+    let draw_line_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("pipeline_layout:draw_line"),
+            bind_group_layouts: &[uniform_bind_group_layout, view_uniform_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+    let draw_line_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("shader:draw_line"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../../shader/line.wgsl"))),
+    });
+
+    let vertex_buffer_layouts = [
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Line>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2, 1 => Float32x2, 2 => Float32x4, 3 => Float32x3, 4 => Float32],
+        },
+        wgpu::VertexBufferLayout {
+            array_stride: 2 * std::mem::size_of::<f32>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &wgpu::vertex_attr_array![5 => Float32x2],
+        },
+        wgpu::VertexBufferLayout {
+            array_stride: 2 * std::mem::size_of::<f32>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![6 => Float32x2],
+        },
+    ];
+
+    let color_targets = [Some(wgpu::ColorTargetState {
+        format: target_format,
+        blend: Some(get_blend_state(blend_mode)),
+        write_mask: wgpu::ColorWrites::ALL,
+    })];
+
+    let draw_line_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("pipeline:draw_line"),
+        layout: Some(&draw_line_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &draw_line_shader,
+            entry_point: Some("main_vs"),
+            buffers: &vertex_buffer_layouts,
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &draw_line_shader,
+            entry_point: Some("main_fs"),
+            targets: &color_targets,
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    });
+
+    let draw_endpoint_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("pipeline_layout:draw_endpoint"),
+            bind_group_layouts: &[
+                uniform_bind_group_layout,
+                view_uniform_bind_group_layout,
+                endpoint_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+    let draw_endpoint_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("shader:draw_endpoint"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../../shader/endpoint.wgsl"))),
+    });
+
+    // TODO: reuse draw_line layout
+    let draw_endpoint_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("pipeline:draw_endpoint"),
+        layout: Some(&draw_endpoint_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &draw_endpoint_shader,
+            entry_point: Some("main_vs"),
+            buffers: &vertex_buffer_layouts,
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &draw_endpoint_shader,
+            entry_point: Some("main_fs"),
+            targets: &color_targets,
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    });
+
+    (draw_line_pipeline, draw_endpoint_pipeline)
+}
+
+/// The shaders output premultiplied colors.
+pub(super) fn get_blend_state(blend_mode: BlendMode) -> wgpu::BlendState {
+    let blend_component = |src_factor, dst_factor, operation| wgpu::BlendComponent {
+        src_factor,
+        dst_factor,
+        operation,
+    };
+
+    use wgpu::BlendFactor::*;
+    use wgpu::BlendOperation::*;
+    match blend_mode {
+        BlendMode::Additive => wgpu::BlendState {
+            color: blend_component(One, One, Add),
+            alpha: blend_component(One, One, Add),
+        },
+        BlendMode::Alpha => wgpu::BlendState {
+            color: blend_component(One, OneMinusSrcAlpha, Add),
+            alpha: blend_component(One, OneMinusSrcAlpha, Add),
+        },
+        // src + dst * (1 - src)
+        BlendMode::Screen => wgpu::BlendState {
+            color: blend_component(One, OneMinusSrc, Add),
+            alpha: blend_component(One, OneMinusSrcAlpha, Add),
+        },
+        BlendMode::Max => wgpu::BlendState {
+            color: blend_component(One, One, Max),
+            alpha: blend_component(One, One, Max),
+        },
+    }
+}
+
 fn get_line_scale_factor(width: f32, height: f32) -> f32 {
     let aspect_ratio = width / height;
     let p = 1.0 / aspect_ratio;
     1.0 / ((1.0 - p) * width + p * height).min(2000.0)
 }
 
+/// Build a strip of quads from the base of the line (y = 0) to its tip (y = 1).
+/// A single segment gives the straight line in `LINE_VERTICES`.
+pub fn create_line_vertices(segments: u32) -> Vec<f32> {
+    (0..segments)
+        .flat_map(|i| {
+            let y0 = i as f32 / segments as f32;
+            let y1 = (i + 1) as f32 / segments as f32;
+            [-0.5, y0, -0.5, y1, 0.5, y1, -0.5, y0, 0.5, y1, 0.5, y0]
+        })
+        .collect()
+}
+
+fn create_line_vertex_buffer(device: &wgpu::Device, segments: u32) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("buffer:vertices"),
+        contents: bytemuck::cast_slice(&create_line_vertices(segments)),
+        usage: wgpu::BufferUsages::VERTEX,
+    })
+}
+
 #[rustfmt::skip]
 pub static LINE_VERTICES: [f32; 12] = [
     -0.5, 0.0,
@@ -922,6 +1198,16 @@ pub static LINE_VERTICES: [f32; 12] = [
      0.5, 0.0,
 ];
 
+// Only segmented lines bend. A single segment has vertices at the ends of the
+// line only, where bending would just skew the quad.
+fn get_line_curvature(settings: &Settings) -> f32 {
+    if settings.line_segments > 1 {
+        settings.line_curvature.max(0.0)
+    } else {
+        0.0
+    }
+}
+
 #[rustfmt::skip]
 pub static ENDPOINT_VERTICES: [f32; 12] = [
     -1.0, -1.0,
@@ -931,3 +1217,51 @@ pub static ENDPOINT_VERTICES: [f32; 12] = [
     -1.0,  1.0,
      1.0,  1.0,
 ];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn single_segment_is_a_straight_line() {
+        assert_eq!(create_line_vertices(1), LINE_VERTICES);
+    }
+
+    #[test]
+    fn single_segment_lines_dont_bend() {
+        // Without a bend or taper, the vertex shader draws the same quad as
+        // the original straight lines
+        let settings = Settings::default();
+        assert_eq!(settings.line_segments, 1);
+        assert!(settings.line_curvature > 0.0);
+
+        let size = wgpu::Extent3d {
+            width: 1280,
+            height: 720,
+            depth_or_array_layers: 1,
+        };
+        let grid = Grid::new(size.width, size.height, &settings);
+        let uniforms = LineUniforms::from_settings(size, &grid, &settings, false);
+        assert_eq!(uniforms.line_curvature, 0.0);
+        assert_eq!(uniforms.line_taper, 0.0);
+    }
+
+    #[test]
+    fn segmented_lines_bend() {
+        let settings = Settings {
+            line_segments: 8,
+            ..Default::default()
+        };
+        assert_eq!(get_line_curvature(&settings), settings.line_curvature);
+    }
+
+    #[test]
+    fn segments_span_the_whole_line() {
+        let vertices = create_line_vertices(4);
+        assert_eq!(vertices.len(), 4 * LINE_VERTICES.len());
+
+        let ys: Vec<f32> = vertices.iter().skip(1).step_by(2).copied().collect();
+        assert_eq!(ys.iter().copied().fold(f32::MAX, f32::min), 0.0);
+        assert_eq!(ys.iter().copied().fold(f32::MIN, f32::max), 1.0);
+    }
+}
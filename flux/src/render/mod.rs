@@ -1,13 +1,69 @@
+pub mod background;
+pub mod bloom;
 pub mod color;
+pub mod composite;
+pub mod dye;
+pub mod endpoint;
+pub mod exposure;
+pub mod fade;
 pub mod fluid;
 pub mod lines;
+pub mod msaa;
 pub mod noise;
+pub mod particles;
 pub mod texture;
+pub mod trails;
 pub mod view;
 
+use crate::settings::ColorMode;
+
+pub use view::SafeZones;
 pub use view::ScreenViewport;
 pub use view::ViewTransform;
 
+/// Whether a render target expects linear colors, rather than sRGB-encoded
+/// ones. sRGB formats encode on write, and float formats hold linear or
+/// extended-range values.
+pub fn is_linear_format(format: wgpu::TextureFormat) -> bool {
+    format.is_srgb()
+        || matches!(
+            format,
+            wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgba32Float
+        )
+}
+
+/// The color wheel of the current preset, and the number of colors in it.
+/// Without one, the colors are derived from the velocity, like the "Original"
+/// preset. Colors sampled from images fall back to these too.
+pub fn get_palette(color_mode: &ColorMode) -> ([[f32; 4]; 6], u32) {
+    let color_wheel = match color_mode {
+        ColorMode::Preset(preset) => preset.to_color_wheel(),
+        _ => None,
+    };
+
+    let mut palette = [[0.0; 4]; 6];
+    let Some(color_wheel) = color_wheel else {
+        return (palette, 0);
+    };
+    for (color, rgba) in palette.iter_mut().zip(color_wheel.chunks_exact(4)) {
+        color.copy_from_slice(rgba);
+    }
+    (palette, palette.len() as u32)
+}
+
+/// A device for testing a single context, on any adapter. None where there is
+/// no adapter, like on CI machines without a GPU.
+#[cfg(test)]
+pub fn request_test_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&Default::default())).ok()?;
+    pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+        required_limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+        ..Default::default()
+    }))
+    .ok()
+}
+
 // pub struct GraphicsContext {
 //     pub device: wgpu::Device,
 //     pub queue: wgpu::Queue,
@@ -0,0 +1,74 @@
+use super::exposure::SCENE_FORMAT;
+use crate::settings::{Antialiasing, Settings};
+
+pub const SAMPLE_COUNT: u32 = 4;
+
+// The scale to supersample at when multisampling isn't available
+const FALLBACK_SUPERSAMPLE_SCALE: f32 = 2.0;
+
+/// The number of samples per pixel to draw the lines with.
+pub fn get_sample_count(settings: &Settings) -> u32 {
+    match settings.antialiasing {
+        Antialiasing::Msaa4x => SAMPLE_COUNT,
+        _ => 1,
+    }
+}
+
+/// Whether the adapter can multisample and resolve the offscreen scene.
+pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+    let features = adapter.get_texture_format_features(SCENE_FORMAT);
+    features.flags.sample_count_supported(SAMPLE_COUNT)
+        && features
+            .flags
+            .contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE)
+}
+
+/// Swap multisampling for supersampling on adapters that don't support it.
+pub fn fallback_if_unsupported(
+    adapter: &wgpu::Adapter,
+    antialiasing: Antialiasing,
+) -> Antialiasing {
+    if antialiasing == Antialiasing::Msaa4x && !is_supported(adapter) {
+        log::warn!("MSAA isn't supported by this adapter. Falling back to supersampling.");
+        return Antialiasing::Supersample(FALLBACK_SUPERSAMPLE_SCALE);
+    }
+
+    antialiasing
+}
+
+/// The multisampled target the lines are drawn into before being resolved to
+/// the scene.
+pub struct Context {
+    texture_view: wgpu::TextureView,
+}
+
+impl Context {
+    pub fn resize(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) {
+        self.texture_view = create_texture_view(device, size);
+    }
+
+    pub fn get_texture_view(&self) -> &wgpu::TextureView {
+        &self.texture_view
+    }
+
+    pub fn new(device: &wgpu::Device, size: wgpu::Extent3d) -> Self {
+        Self {
+            texture_view: create_texture_view(device, size),
+        }
+    }
+}
+
+fn create_texture_view(device: &wgpu::Device, size: wgpu::Extent3d) -> wgpu::TextureView {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("texture:multisampled_scene"),
+        size,
+        mip_level_count: 1,
+        sample_count: SAMPLE_COUNT,
+        dimension: wgpu::TextureDimension::D2,
+        format: SCENE_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
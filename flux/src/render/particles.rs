@@ -0,0 +1,472 @@
+use crate::render::view::ViewTransform;
+use crate::rng;
+use crate::settings::{BlendMode, Settings};
+
+use rand::Rng;
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParticleUniforms {
+    view_matrix: [[f32; 4]; 4], // 0
+    // sRGB colors. Unused entries are zeroed.
+    palette: [[f32; 4]; 6], // 64
+    palette_size: u32,      // 160
+    linear_output: u32,     // 164
+    // Seeds the respawn positions
+    frame: u32,      // 168
+    delta_time: f32, // 172
+    flow_speed: f32, // 176
+    lifetime: f32,   // 180
+    // The half-size of a particle in clip space
+    size: [f32; 2],  // 184
+    brightness: f32, // 192
+    _padding: [u32; 3], // 196
+                     // roundUp(208, 16) = 208
+}
+
+impl ParticleUniforms {
+    fn new(screen_size: wgpu::Extent3d, settings: &Settings, linear_output: bool) -> Self {
+        let (palette, palette_size) = super::get_palette(&settings.color_mode);
+
+        Self {
+            view_matrix: ViewTransform::default().to_matrix().to_cols_array_2d(),
+            palette,
+            palette_size,
+            linear_output: linear_output.into(),
+            frame: 0,
+            delta_time: 1.0 / 60.0,
+            flow_speed: settings.flow_speed,
+            lifetime: settings.particle_lifetime.max(0.1),
+            size: get_particle_size(screen_size, settings),
+            brightness: settings.base_intensity * settings.brightness_multiplier,
+            _padding: [0; 3],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Particle {
+    position: [f32; 2],
+    velocity: [f32; 2],
+    age: f32,
+    lifetime: f32,
+}
+
+pub struct Context {
+    particle_count: u32,
+    work_group_count: u32,
+    target_format: wgpu::TextureFormat,
+    blend_mode: BlendMode,
+    sample_count: u32,
+
+    uniforms: ParticleUniforms,
+    uniform_buffer: wgpu::Buffer,
+    particle_buffer: wgpu::Buffer,
+
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
+    uniform_bind_group: wgpu::BindGroup,
+    particle_bind_group_layout: wgpu::BindGroupLayout,
+    particle_bind_group: wgpu::BindGroup,
+
+    update_pipeline: wgpu::ComputePipeline,
+    draw_pipeline: wgpu::RenderPipeline,
+}
+
+impl Context {
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        screen_size: wgpu::Extent3d,
+        settings: &Settings,
+    ) {
+        let particle_count = settings.particle_count.max(1);
+        if particle_count != self.particle_count {
+            self.particle_count = particle_count;
+            self.work_group_count = particle_count.div_ceil(64);
+            self.particle_buffer = create_particle_buffer(device, particle_count, settings);
+            self.particle_bind_group = create_particle_bind_group(
+                device,
+                &self.particle_bind_group_layout,
+                &self.particle_buffer,
+            );
+        }
+
+        let sample_count = super::msaa::get_sample_count(settings);
+        if settings.blend_mode != self.blend_mode || sample_count != self.sample_count {
+            self.blend_mode = settings.blend_mode;
+            self.sample_count = sample_count;
+            self.rebuild_draw_pipeline(device);
+        }
+
+        let linear_output = super::is_linear_format(self.target_format);
+        self.uniforms = ParticleUniforms {
+            frame: self.uniforms.frame,
+            ..ParticleUniforms::new(screen_size, settings, linear_output)
+        };
+        self.write_uniforms(queue);
+    }
+
+    pub fn resize(
+        &mut self,
+        queue: &wgpu::Queue,
+        screen_size: wgpu::Extent3d,
+        settings: &Settings,
+    ) {
+        self.uniforms.size = get_particle_size(screen_size, settings);
+        self.write_uniforms(queue);
+    }
+
+    /// Rebuild the draw pipeline to render into a texture of a different format.
+    pub fn set_target_format(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target_format: wgpu::TextureFormat,
+    ) {
+        self.target_format = target_format;
+        self.rebuild_draw_pipeline(device);
+
+        self.uniforms.linear_output = super::is_linear_format(target_format).into();
+        self.write_uniforms(queue);
+    }
+
+    pub fn tick(&mut self, queue: &wgpu::Queue, timestep: f32) {
+        self.uniforms.frame = self.uniforms.frame.wrapping_add(1);
+        self.uniforms.delta_time = timestep;
+        self.write_uniforms(queue);
+    }
+
+    pub fn set_view_transform(&self, queue: &wgpu::Queue, view_transform: ViewTransform) {
+        // The view matrix comes first in the uniforms
+        let view_matrix = view_transform.to_matrix().to_cols_array_2d();
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&view_matrix));
+    }
+
+    /// Carry the particles along with the fluid, respawning any that expire.
+    pub fn advect<'cpass>(
+        &'cpass self,
+        cpass: &mut wgpu::ComputePass<'cpass>,
+        velocity_bind_group: &'cpass wgpu::BindGroup,
+    ) {
+        cpass.set_pipeline(&self.update_pipeline);
+        cpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        cpass.set_bind_group(1, &self.particle_bind_group, &[]);
+        cpass.set_bind_group(2, velocity_bind_group, &[]);
+        cpass.dispatch_workgroups(self.work_group_count, 1, 1);
+    }
+
+    pub fn draw<'rpass>(&'rpass self, rpass: &mut wgpu::RenderPass<'rpass>) {
+        rpass.set_pipeline(&self.draw_pipeline);
+        rpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.particle_buffer.slice(..));
+        rpass.draw(0..6, 0..self.particle_count);
+    }
+
+    fn write_uniforms(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniforms]),
+        );
+    }
+
+    fn rebuild_draw_pipeline(&mut self, device: &wgpu::Device) {
+        self.draw_pipeline = create_draw_pipeline(
+            device,
+            self.target_format,
+            self.blend_mode,
+            self.sample_count,
+            &self.uniform_bind_group_layout,
+        );
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        screen_size: wgpu::Extent3d,
+        settings: &Settings,
+    ) -> Self {
+        let particle_count = settings.particle_count.max(1);
+        let sample_count = super::msaa::get_sample_count(settings);
+        let linear_output = super::is_linear_format(target_format);
+        let uniforms = ParticleUniforms::new(screen_size, settings, linear_output);
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("buffer:ParticleUniforms"),
+            contents: bytemuck::cast_slice(&[uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let particle_buffer = create_particle_buffer(device, particle_count, settings);
+
+        let linear_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("sampler:particles"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            ..Default::default()
+        });
+
+        let uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout:particle_uniforms"),
+                entries: &[
+                    // uniforms
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE
+                            | wgpu::ShaderStages::VERTEX
+                            | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // linear_sampler
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bind_group:particle_uniforms"),
+            layout: &uniform_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&linear_sampler),
+                },
+            ],
+        });
+
+        let particle_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout:particles"),
+                entries: &[
+                    // particles
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let particle_bind_group =
+            create_particle_bind_group(device, &particle_bind_group_layout, &particle_buffer);
+
+        // TODO: reuse layout from fluid
+        let velocity_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout:particle_velocity"),
+                entries: &[
+                    // velocity_texture
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    // out_velocity_texture
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: wgpu::TextureFormat::Rg32Float,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader:particles"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../../shader/particles.wgsl"
+            ))),
+        });
+
+        let update_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("pipeline_layout:update_particles"),
+                bind_group_layouts: &[
+                    &uniform_bind_group_layout,
+                    &particle_bind_group_layout,
+                    &velocity_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let update_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("pipeline:update_particles"),
+            layout: Some(&update_pipeline_layout),
+            module: &shader,
+            entry_point: Some("update"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let draw_pipeline = create_draw_pipeline(
+            device,
+            target_format,
+            settings.blend_mode,
+            sample_count,
+            &uniform_bind_group_layout,
+        );
+
+        Self {
+            particle_count,
+            work_group_count: particle_count.div_ceil(64),
+            target_format,
+            blend_mode: settings.blend_mode,
+            sample_count,
+
+            uniforms,
+            uniform_buffer,
+            particle_buffer,
+
+            uniform_bind_group_layout,
+            uniform_bind_group,
+            particle_bind_group_layout,
+            particle_bind_group,
+
+            update_pipeline,
+            draw_pipeline,
+        }
+    }
+}
+
+fn get_particle_size(screen_size: wgpu::Extent3d, settings: &Settings) -> [f32; 2] {
+    let size = settings.particle_size.max(0.0);
+    [
+        size / screen_size.width.max(1) as f32,
+        size / screen_size.height.max(1) as f32,
+    ]
+}
+
+/// Scatter the particles across the screen. Their ages are staggered so that
+/// they don't all respawn at once.
+fn create_particle_buffer(
+    device: &wgpu::Device,
+    particle_count: u32,
+    settings: &Settings,
+) -> wgpu::Buffer {
+    let mut rng = rng::seeded("particles");
+    let lifetime = settings.particle_lifetime.max(0.1);
+    let particles: Vec<Particle> = (0..particle_count)
+        .map(|_| {
+            let particle_lifetime = lifetime * rng.random_range(0.5..1.5);
+            Particle {
+                position: [rng.random(), rng.random()],
+                velocity: [0.0; 2],
+                age: particle_lifetime * rng.random::<f32>(),
+                lifetime: particle_lifetime,
+            }
+        })
+        .collect();
+
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("buffer:particles"),
+        contents: bytemuck::cast_slice(&particles),
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+    })
+}
+
+fn create_particle_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    particle_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("bind_group:particles"),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: particle_buffer.as_entire_binding(),
+        }],
+    })
+}
+
+fn create_draw_pipeline(
+    device: &wgpu::Device,
+    target_format: wgpu::TextureFormat,
+    blend_mode: BlendMode,
+    sample_count: u32,
+    uniform_bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("shader:draw_particles"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+            "../../shader/particles.wgsl"
+        ))),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("pipeline_layout:draw_particles"),
+        bind_group_layouts: &[uniform_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("pipeline:draw_particles"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("main_vs"),
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<Particle>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &wgpu::vertex_attr_array![
+                    0 => Float32x2,
+                    1 => Float32x2,
+                    2 => Float32,
+                    3 => Float32,
+                ],
+            }],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("main_fs"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: target_format,
+                blend: Some(super::lines::get_blend_state(blend_mode)),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    })
+}
@@ -0,0 +1,241 @@
+use super::exposure::SCENE_FORMAT;
+use crate::settings::{Mode, Settings};
+
+use std::borrow::Cow;
+
+// Stop just short of keeping everything. Half floats can't represent the
+// difference anyway, and the trails would never fade.
+const MAX_DECAY: f32 = 0.999;
+
+// Particles always leave trails. This is used unless a decay is set.
+const PARTICLE_DECAY: f32 = 0.9;
+
+pub struct Context {
+    decay: f32,
+    sample_count: u32,
+    // The fraction of the previous frame to keep this frame
+    keep: f32,
+
+    accumulation_texture_view: wgpu::TextureView,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+
+    fade_pipeline: wgpu::RenderPipeline,
+    blit_pipeline: wgpu::RenderPipeline,
+}
+
+impl Context {
+    pub fn update(&mut self, settings: &Settings) {
+        self.decay = get_decay(settings);
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) {
+        // The trails are lost, but the new texture starts out black
+        self.accumulation_texture_view = create_accumulation_texture_view(device, size);
+        self.bind_group = create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.accumulation_texture_view,
+        );
+    }
+
+    pub fn tick(&mut self, timestep: f32) {
+        // The decay is specified at 60 fps. Scale it to the frame time.
+        self.keep = self.decay.powf(60.0 * timestep);
+    }
+
+    /// The lines are multisampled into a separate target when MSAA is on. The
+    /// trails are kept there and resolved into the accumulation texture.
+    pub fn get_sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn get_accumulation_texture_view(&self) -> &wgpu::TextureView {
+        &self.accumulation_texture_view
+    }
+
+    /// Fade out the previous frame in the accumulation texture.
+    pub fn fade<'rpass>(&'rpass self, rpass: &mut wgpu::RenderPass<'rpass>) {
+        let keep = self.keep as f64;
+        rpass.set_pipeline(&self.fade_pipeline);
+        rpass.set_blend_constant(wgpu::Color {
+            r: keep,
+            g: keep,
+            b: keep,
+            a: keep,
+        });
+        rpass.draw(0..3, 0..1);
+    }
+
+    /// Copy the accumulated frames to the render target.
+    pub fn draw<'rpass>(&'rpass self, rpass: &mut wgpu::RenderPass<'rpass>) {
+        rpass.set_pipeline(&self.blit_pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+
+    pub fn new(device: &wgpu::Device, size: wgpu::Extent3d, settings: &Settings) -> Self {
+        let decay = get_decay(settings);
+        let sample_count = super::msaa::get_sample_count(settings);
+
+        let accumulation_texture_view = create_accumulation_texture_view(device, size);
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bind_group_layout:trails"),
+            entries: &[
+                // accumulation_texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = create_bind_group(device, &bind_group_layout, &accumulation_texture_view);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader:trails"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../../shader/trails.wgsl"
+            ))),
+        });
+
+        // Nothing is bound while fading. The accumulation texture is the
+        // render target.
+        let fade_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("pipeline_layout:fade_trails"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let fade_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("pipeline:fade_trails"),
+            layout: Some(&fade_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("main_vs"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fade"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: SCENE_FORMAT,
+                    // dst * keep
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::Constant,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent::REPLACE,
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let blit_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("pipeline_layout:blit_trails"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let blit_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("pipeline:blit_trails"),
+            layout: Some(&blit_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("main_vs"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("blit"),
+                targets: &[Some(SCENE_FORMAT.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            decay,
+            sample_count,
+            keep: decay,
+
+            accumulation_texture_view,
+            bind_group_layout,
+            bind_group,
+
+            fade_pipeline,
+            blit_pipeline,
+        }
+    }
+}
+
+/// Whether the previous frames are kept and faded out.
+pub fn is_enabled(settings: &Settings) -> bool {
+    get_decay(settings) > 0.0
+}
+
+fn get_decay(settings: &Settings) -> f32 {
+    let decay = match settings.mode {
+        Mode::Particles if settings.trail_decay <= 0.0 => PARTICLE_DECAY,
+        _ => settings.trail_decay,
+    };
+    decay.clamp(0.0, MAX_DECAY)
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    accumulation_texture_view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("bind_group:trails"),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(accumulation_texture_view),
+        }],
+    })
+}
+
+fn create_accumulation_texture_view(
+    device: &wgpu::Device,
+    size: wgpu::Extent3d,
+) -> wgpu::TextureView {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("texture:trails"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: SCENE_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
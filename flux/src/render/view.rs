@@ -24,10 +24,31 @@ impl ScreenViewport {
     }
 }
 
+/// The parts of the screen covered by system UI, like a menu bar or taskbar,
+/// as fractions of the screen size from each edge.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SafeZones {
+    pub top: f32,
+    pub bottom: f32,
+    pub left: f32,
+    pub right: f32,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ViewTransform {
     pub offset: [f32; 2],
     pub scale: [f32; 2],
+    /// Zoom into the canvas around `pan`, before rotating it
+    pub zoom: f32,
+    /// The point on the canvas, in clip space, to center the view on
+    pub pan: [f32; 2],
+    /// Counter-clockwise, in radians, around the center of the canvas
+    pub rotation: f32,
+    /// Flip the canvas horizontally and vertically
+    pub mirror: [bool; 2],
+    /// The aspect ratio of the canvas. Rotations happen in square units, so
+    /// that they don't stretch the canvas.
+    pub aspect: f32,
 }
 
 impl Default for ViewTransform {
@@ -35,6 +56,11 @@ impl Default for ViewTransform {
         Self {
             offset: [0.0, 0.0],
             scale: [1.0, 1.0],
+            zoom: 1.0,
+            pan: [0.0, 0.0],
+            rotation: 0.0,
+            mirror: [false, false],
+            aspect: 1.0,
         }
     }
 }
@@ -53,6 +79,15 @@ impl RelativeEq for ViewTransform {
         self.offset
             .relative_eq(&other.offset, epsilon, max_relative)
             && self.scale.relative_eq(&other.scale, epsilon, max_relative)
+            && self.zoom.relative_eq(&other.zoom, epsilon, max_relative)
+            && self.pan.relative_eq(&other.pan, epsilon, max_relative)
+            && self
+                .rotation
+                .relative_eq(&other.rotation, epsilon, max_relative)
+            && self.mirror == other.mirror
+            && self
+                .aspect
+                .relative_eq(&other.aspect, epsilon, max_relative)
     }
 }
 
@@ -74,18 +109,47 @@ impl ViewTransform {
             -scale_dy + (viewport.y as f32 * 2.0 / screen_height) * scale[1],
         ];
 
-        Self { offset, scale }
+        Self {
+            offset,
+            scale,
+            ..Default::default()
+        }
     }
 
     pub fn to_matrix(&self) -> glam::Mat4 {
-        glam::Mat4::from_scale_rotation_translation(
+        let viewport = glam::Mat4::from_scale_rotation_translation(
             glam::Vec3::new(self.scale[0], self.scale[1], 1.0),
             glam::Quat::IDENTITY,
             glam::Vec3::new(self.offset[0], self.offset[1], 0.0),
-        )
+        );
+
+        // Rotate in square units, then zoom in until the rotated canvas
+        // covers the corners of the screen.
+        let aspect = self.aspect.max(f32::EPSILON);
+        let fill = get_fill_scale(self.rotation, aspect);
+        let flip = |mirror: bool| if mirror { -1.0 } else { 1.0 };
+
+        viewport
+            * glam::Mat4::from_scale(glam::Vec3::new(1.0 / aspect, 1.0, 1.0))
+            * glam::Mat4::from_rotation_z(self.rotation)
+            * glam::Mat4::from_scale(glam::Vec3::new(
+                aspect * fill * flip(self.mirror[0]),
+                fill * flip(self.mirror[1]),
+                1.0,
+            ))
+            * glam::Mat4::from_scale(glam::Vec3::new(self.zoom, self.zoom, 1.0))
+            * glam::Mat4::from_translation(glam::Vec3::new(-self.pan[0], -self.pan[1], 0.0))
     }
 }
 
+/// The zoom at which a canvas, rotated by `rotation`, still covers the whole
+/// screen.
+fn get_fill_scale(rotation: f32, aspect: f32) -> f32 {
+    let (sin, cos) = rotation.sin_cos();
+    let (sin, cos) = (sin.abs(), cos.abs());
+    f32::max((aspect * cos + sin) / aspect, aspect * sin + cos)
+}
+
 impl AbsDiffEq for ViewTransform {
     type Epsilon = f32;
 
@@ -96,6 +160,11 @@ impl AbsDiffEq for ViewTransform {
     fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
         self.offset.abs_diff_eq(&other.offset, epsilon)
             && self.scale.abs_diff_eq(&other.scale, epsilon)
+            && self.zoom.abs_diff_eq(&other.zoom, epsilon)
+            && self.pan.abs_diff_eq(&other.pan, epsilon)
+            && self.rotation.abs_diff_eq(&other.rotation, epsilon)
+            && self.mirror == other.mirror
+            && self.aspect.abs_diff_eq(&other.aspect, epsilon)
     }
 }
 
@@ -131,6 +200,7 @@ mod test {
             ViewTransform {
                 offset: [1.34375, 0.0],
                 scale: [2.34375, 1.0],
+                ..Default::default()
             },
         );
         assert_view_transform(
@@ -139,6 +209,7 @@ mod test {
             ViewTransform {
                 offset: [-0.74418604, 0.0],
                 scale: [1.744186, 1.0],
+                ..Default::default()
             },
         );
     }
@@ -156,6 +227,7 @@ mod test {
             ViewTransform {
                 offset: [1.0, 0.0],
                 scale: [2.0, 1.0],
+                ..Default::default()
             },
         );
         assert_view_transform(
@@ -164,6 +236,7 @@ mod test {
             ViewTransform {
                 offset: [-1.0, 0.0],
                 scale: [2.0, 1.0],
+                ..Default::default()
             },
         );
     }
@@ -181,6 +254,7 @@ mod test {
             ViewTransform {
                 offset: [0.0, 0.0],
                 scale: [1.0, 1.0],
+                ..Default::default()
             },
         );
     }
@@ -198,6 +272,7 @@ mod test {
             ViewTransform {
                 offset: [1.0, -1.0],
                 scale: [2.0, 2.0],
+                ..Default::default()
             },
         );
     }
@@ -215,6 +290,7 @@ mod test {
             ViewTransform {
                 offset: [0.75, -0.5],
                 scale: [2.0, 2.0],
+                ..Default::default()
             },
         );
     }
@@ -232,6 +308,7 @@ mod test {
             ViewTransform {
                 offset: [0.0, 0.0],
                 scale: [1.0, 2.0],
+                ..Default::default()
             },
         );
     }
@@ -249,6 +326,7 @@ mod test {
             ViewTransform {
                 offset: [0.0, 0.0],
                 scale: [2.0, 1.0],
+                ..Default::default()
             },
         );
     }
@@ -266,7 +344,73 @@ mod test {
             ViewTransform {
                 offset: [0.0, 0.0],
                 scale: [4.0, 3.0],
+                ..Default::default()
             },
         );
     }
+
+    #[test]
+    fn test_rotation_keeps_square_units() {
+        let view_transform = ViewTransform {
+            rotation: std::f32::consts::FRAC_PI_2,
+            aspect: 2.0,
+            ..Default::default()
+        };
+        let matrix = view_transform.to_matrix();
+
+        // A quarter turn sends the right edge to the top, without stretching.
+        // The canvas is zoomed in to cover the screen.
+        let point = matrix.transform_point3(glam::Vec3::new(0.5, 0.0, 0.0));
+        assert_relative_eq!(point.x, 0.0, epsilon = 1e-6);
+        assert_relative_eq!(point.y, 2.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_rotated_canvas_covers_screen() {
+        let aspect = 16.0 / 9.0;
+        for degrees in [10.0_f32, 45.0, 90.0, 135.0] {
+            let view_transform = ViewTransform {
+                rotation: degrees.to_radians(),
+                aspect,
+                ..Default::default()
+            };
+            let inverse = view_transform.to_matrix().inverse();
+            for corner in [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]] {
+                let point = inverse.transform_point3(glam::Vec3::new(corner[0], corner[1], 0.0));
+                assert!(point.x.abs() <= 1.0 + 1e-5, "{} at {}°", point, degrees);
+                assert!(point.y.abs() <= 1.0 + 1e-5, "{} at {}°", point, degrees);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zoom_around_pan() {
+        let view_transform = ViewTransform {
+            zoom: 2.0,
+            pan: [0.25, -0.5],
+            ..Default::default()
+        };
+        let matrix = view_transform.to_matrix();
+
+        let center = matrix.transform_point3(glam::Vec3::new(0.25, -0.5, 0.0));
+        assert_relative_eq!(center.x, 0.0);
+        assert_relative_eq!(center.y, 0.0);
+
+        let corner = matrix.transform_point3(glam::Vec3::new(0.75, 0.0, 0.0));
+        assert_relative_eq!(corner.x, 1.0);
+        assert_relative_eq!(corner.y, 1.0);
+    }
+
+    #[test]
+    fn test_mirror() {
+        let view_transform = ViewTransform {
+            mirror: [true, false],
+            ..Default::default()
+        };
+        let point = view_transform
+            .to_matrix()
+            .transform_point3(glam::Vec3::new(0.5, 0.25, 0.0));
+        assert_relative_eq!(point.x, -0.5);
+        assert_relative_eq!(point.y, 0.25);
+    }
 }
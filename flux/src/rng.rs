@@ -12,6 +12,7 @@ use std::cell::RefCell;
 use std::thread_local;
 
 thread_local!(
+    static FLUX_SEED: RefCell<String> = const { RefCell::new(String::new()) };
     static FLUX_RNG: RefCell<Pcg32> = {
         let rng = Pcg32::from_rng(&mut rand::rng());
         RefCell::new(rng)
@@ -27,7 +28,15 @@ pub fn init_from_seed(optional_seed: &Option<String>) {
             .collect()
     });
 
-    FLUX_RNG.with(|rng| rng.replace(Seeder::from(seed).into_rng()));
+    FLUX_RNG.with(|rng| rng.replace(Seeder::from(&seed).into_rng()));
+    FLUX_SEED.with(|flux_seed| flux_seed.replace(seed));
+}
+
+/// Create a separate RNG, derived from the current seed and a label. It
+/// doesn't advance the shared RNG, so the same seed and label always produce
+/// the same numbers, no matter how many times it's called.
+pub fn seeded(label: &str) -> Pcg32 {
+    FLUX_SEED.with(|seed| Seeder::from(format!("{}:{}", seed.borrow(), label)).into_rng())
 }
 
 pub fn gen<T>() -> T
@@ -16,13 +16,72 @@ pub struct Settings {
     pub pressure_iterations: u32,
 
     pub color_mode: ColorMode,
+    /// Color each line by the local flow, rather than the palette alone
+    pub color_mapping: ColorMapping,
 
     pub line_length: f32,
     pub line_width: f32,
     pub line_begin_offset: f32,
     pub line_variance: f32,
+    /// Split each line into this many segments so that it can bend with the
+    /// flow. A single segment draws a straight line (default: 1)
+    pub line_segments: u32,
+    /// How far segmented lines bend behind their direction of motion. The bend
+    /// is a parabola traced from the current velocity of the tip. Lines with a
+    /// single segment stay straight (default: 1.0)
+    pub line_curvature: f32,
+    /// Narrow the lines towards their base. 0.0 keeps an even width (default: 0.0)
+    pub line_taper: f32,
+    /// What to draw at the tip of each line
+    pub endpoint_style: EndpointStyle,
+    /// The size of the tips, relative to the width of the line. Lines widen
+    /// as the flow speeds up, and the tips grow with them (default: 1.0)
+    pub endpoint_size: f32,
+    /// The opacity of the tips, relative to the line (default: 1.0)
+    pub endpoint_opacity: f32,
     pub grid_spacing: u32,
+    /// The most lines to draw, shared by the main lines and the layers. The
+    /// grid spacing is increased to stay under this on very large screens.
+    /// Unlimited if `None` (default: 100000)
+    pub max_lines: Option<u32>,
+    /// How the line basepoints are arranged on the screen
+    pub grid_layout: GridLayout,
+    /// Place fewer lines in some parts of the screen than in others
+    pub line_density: LineDensity,
+    /// Extra layers of lines, drawn behind the main ones
+    pub layers: Vec<LineLayer>,
     pub view_scale: f32,
+    /// Rotate the view counter-clockwise, in degrees (default: 0.0)
+    pub view_rotation: f32,
+    /// Keep turning the view, in degrees per second (default: 0.0)
+    pub rotation_speed: f32,
+    /// Flip the view horizontally
+    pub mirror_horizontal: bool,
+    /// Flip the view vertically
+    pub mirror_vertical: bool,
+    /// Fold the view into this many mirrored wedges around the center.
+    /// Disabled if `None` (default: None)
+    pub kaleidoscope: Option<u32>,
+    /// Slowly pan and zoom across the lines. Disabled if `None` (default: None)
+    pub camera_drift: Option<CameraDrift>,
+    /// Draw the lines at a fraction of the physical resolution and upscale
+    /// them to the screen (default: 1.0)
+    pub render_scale: f32,
+    /// How to upscale the lines when the render scale is below 1.0
+    pub upscale_filter: UpscaleFilter,
+
+    /// The number of particles in `Mode::Particles` (default: 20000)
+    pub particle_count: u32,
+    /// The size of the particles, in logical pixels (default: 2.0)
+    pub particle_size: f32,
+    /// How long a particle drifts before it respawns, in seconds (default: 8.0)
+    pub particle_lifetime: f32,
+    /// How fast the particles and dye are carried by the fluid (default: 0.25)
+    pub flow_speed: f32,
+    /// How quickly the dye in `Mode::Dye` fades, per second (default: 0.5)
+    pub dye_dissipation: f32,
+    /// How much dye the moving fluid picks up, per second (default: 0.2)
+    pub dye_injection: f32,
 
     pub noise_multiplier: f32,
     pub noise_channels: Vec<Noise>,
@@ -30,6 +89,68 @@ pub struct Settings {
     /// User brightness multiplier (default: 1.0)
     /// Values < 1.0 dim, values > 1.0 brighten
     pub brightness_multiplier: f32,
+
+    /// Upper bound on line saturation, in HSL (default: 0.25)
+    pub max_saturation: f32,
+    /// Upper bound on line lightness, in HSL (default: 0.30)
+    pub max_lightness: f32,
+    /// Base multiplier applied to line colors before blending (default: 0.3)
+    pub base_intensity: f32,
+    /// How overlapping lines combine
+    pub blend_mode: BlendMode,
+    /// Smooth line edges beyond what the shaders do
+    pub antialiasing: Antialiasing,
+
+    /// Meter the rendered lines on the GPU and adapt exposure over time,
+    /// instead of estimating brightness from the line count.
+    pub auto_exposure: Option<AutoExposure>,
+
+    /// Draw the lines into a floating-point target and tone map them.
+    /// Presents with extended range when the surface is a float format.
+    pub hdr: bool,
+    pub tone_mapping: ToneMapping,
+    /// Exposure applied before tone mapping (default: 1.0)
+    pub exposure: f32,
+    /// Peak brightness on extended-range surfaces, relative to SDR white (default: 2.0)
+    pub hdr_headroom: f32,
+    /// The color space of the output surface
+    pub color_space: ColorSpace,
+
+    /// Strength of the glow around bright lines. Disabled at 0.0 (default: 0.0)
+    pub bloom_intensity: f32,
+    /// How far the glow spreads, from 0.0 to 1.0 (default: 0.6)
+    pub bloom_radius: f32,
+
+    /// Fade the previous frame instead of clearing it, leaving trails behind
+    /// the lines. The fraction of the previous frame kept each frame at 60 fps.
+    /// Disabled at 0.0 (default: 0.0)
+    pub trail_decay: f32,
+    /// How long to fade in on startup, and to crossfade from the previous
+    /// frame when the lines are laid out again, in seconds. That frame is
+    /// copied to crossfade from, so this needs a render target that allows
+    /// copies, and the lines are laid out a frame later. Disabled at 0.0
+    /// (default: 1.0)
+    pub fade_duration: f32,
+    /// How long numeric settings take to ease to new values, in seconds.
+    /// Changes apply straight away at 0.0 (default: 1.0)
+    pub tween_duration: f32,
+
+    /// Dither the output with blue noise to hide banding in dark gradients
+    pub dither: bool,
+    /// Strength of the animated film grain. Disabled at 0.0 (default: 0.0)
+    pub film_grain: f32,
+    /// Darken the corners of the screen, from 0.0 to 1.0. Disabled at 0.0
+    /// (default: 0.0)
+    pub vignette: f32,
+    /// How much to dim the lines behind system UI, like a menu bar or
+    /// taskbar, from 0.0 to 1.0. The covered areas are set with
+    /// `Flux::set_safe_zones`. Disabled at 0.0 (default: 0.0)
+    pub safe_zone_dimming: f32,
+
+    /// What to draw behind the lines
+    pub background: Background,
+    /// How to composite the lines over the background
+    pub background_blend: BackgroundBlend,
 }
 
 impl Default for Settings {
@@ -46,12 +167,37 @@ impl Default for Settings {
             diffusion_iterations: 3,
             pressure_iterations: 19,
             color_mode: ColorMode::Preset(ColorPreset::Original),
+            color_mapping: ColorMapping::Palette,
             line_length: 202.0,
             line_width: 9.0,
             line_begin_offset: 0.4,
             line_variance: 0.55,
+            line_segments: 1,
+            line_curvature: 1.0,
+            line_taper: 0.0,
+            endpoint_style: EndpointStyle::Disc,
+            endpoint_size: 1.0,
+            endpoint_opacity: 1.0,
             grid_spacing: 15,
+            max_lines: Some(100_000),
+            grid_layout: GridLayout::Rectangular,
+            line_density: LineDensity::Uniform,
+            layers: Vec::new(),
             view_scale: 1.6,
+            view_rotation: 0.0,
+            rotation_speed: 0.0,
+            mirror_horizontal: false,
+            mirror_vertical: false,
+            kaleidoscope: None,
+            camera_drift: None,
+            render_scale: 1.0,
+            upscale_filter: UpscaleFilter::Bilinear,
+            particle_count: 20_000,
+            particle_size: 2.0,
+            particle_lifetime: 8.0,
+            flow_speed: 0.25,
+            dye_dissipation: 0.5,
+            dye_injection: 0.2,
             noise_multiplier: 0.45,
             noise_channels: vec![
                 Noise {
@@ -71,6 +217,297 @@ impl Default for Settings {
                 },
             ],
             brightness_multiplier: 1.0,
+            max_saturation: 0.25,
+            max_lightness: 0.30,
+            base_intensity: 0.3,
+            blend_mode: BlendMode::Additive,
+            antialiasing: Antialiasing::None,
+            auto_exposure: None,
+            hdr: false,
+            tone_mapping: ToneMapping::Reinhard,
+            exposure: 1.0,
+            hdr_headroom: 2.0,
+            color_space: ColorSpace::Srgb,
+            bloom_intensity: 0.0,
+            bloom_radius: 0.6,
+            trail_decay: 0.0,
+            fade_duration: 1.0,
+            tween_duration: 1.0,
+            dither: false,
+            film_grain: 0.0,
+            vignette: 0.0,
+            safe_zone_dimming: 0.0,
+            background: Background::Black,
+            background_blend: BackgroundBlend::Add,
+        }
+    }
+}
+
+/// A set of lines with its own grid and look. Every layer follows the same
+/// fluid as the main lines.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LineLayer {
+    pub grid_spacing: u32,
+    pub line_length: f32,
+    pub line_width: f32,
+    /// Use a different palette from the main lines
+    pub color_preset: Option<ColorPreset>,
+    /// (default: 1.0)
+    pub opacity: f32,
+    pub view_scale: f32,
+    /// How far behind the main lines the layer sits, from 0.0 to 1.0. Farther
+    /// layers are dimmer and slower to react to the flow (default: 0.5)
+    pub depth: f32,
+}
+
+impl Default for LineLayer {
+    fn default() -> Self {
+        Self {
+            grid_spacing: 30,
+            line_length: 150.0,
+            line_width: 6.0,
+            color_preset: None,
+            opacity: 1.0,
+            view_scale: 1.6,
+            depth: 0.5,
+        }
+    }
+}
+
+/// A "Ken Burns" camera that wanders across the lines along a smooth path.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CameraDrift {
+    /// How fast the camera moves along its path (default: 1.0)
+    pub speed: f32,
+    /// How far the camera zooms in, as a fraction of the view. It pans
+    /// within the extra room this leaves (default: 0.3)
+    pub range: f32,
+}
+
+impl Default for CameraDrift {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            range: 0.3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AutoExposure {
+    /// The average linear screen luminance to adapt towards.
+    ///
+    /// The default matches the earlier target of 0.04 on sRGB-encoded values.
+    /// That sits on the linear toe of the sRGB curve, so a flat frame meters
+    /// at 0.04 / 12.92 ≈ 0.0031, and the spread between sparse and
+    /// overlapping lines lifts the linear average slightly above that.
+    pub target_luminance: f32,
+    /// The luminance percentile that should stay below white
+    pub highlight_percentile: f32,
+    /// How quickly exposure adapts, per second
+    pub adaptation_rate: f32,
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            target_luminance: 0.0035,
+            highlight_percentile: 0.99,
+            adaptation_rate: 1.5,
+            min_exposure: 0.1,
+            max_exposure: 10.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub enum ToneMapping {
+    /// Clip anything brighter than white
+    Clamp,
+    #[default]
+    Reinhard,
+    /// A fit of the ACES filmic curve
+    Aces,
+}
+
+impl From<ToneMapping> for u32 {
+    fn from(val: ToneMapping) -> Self {
+        match val {
+            ToneMapping::Clamp => 0,
+            ToneMapping::Reinhard => 1,
+            ToneMapping::Aces => 2,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    DisplayP3,
+}
+
+impl From<ColorSpace> for u32 {
+    fn from(val: ColorSpace) -> Self {
+        match val {
+            ColorSpace::Srgb => 0,
+            ColorSpace::DisplayP3 => 1,
+        }
+    }
+}
+
+/// The random layouts are generated from the seed.
+#[derive(Copy, Clone, Default, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub enum GridLayout {
+    #[default]
+    Rectangular,
+    /// Offset every other row by half a column
+    Hexagonal,
+    /// A rectangular grid with each point nudged randomly within its cell
+    Jittered,
+    /// Random points that keep a minimum distance from each other
+    PoissonDisk,
+}
+
+/// The fraction of the grid to keep, from 0.0 to 1.0. The lines that remain
+/// are adjusted so that sparse and dense areas look equally bright.
+#[derive(Clone, Default, Debug, Deserialize, Serialize, PartialEq)]
+pub enum LineDensity {
+    #[default]
+    Uniform,
+    /// Blend from one density at the center of the screen to another at the corners
+    Radial { center: f32, edge: f32 },
+    /// A grayscale image, stretched to the screen. White keeps every line.
+    Image(std::path::PathBuf),
+}
+
+#[derive(Copy, Clone, Default, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub enum BlendMode {
+    /// Add up the light from overlapping lines
+    #[default]
+    Additive,
+    /// Draw lines over each other
+    Alpha,
+    /// Like additive, but saturates gently on dense grids
+    Screen,
+    /// Keep the brightest line
+    Max,
+}
+
+#[derive(Copy, Clone, Default, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub enum UpscaleFilter {
+    #[default]
+    Bilinear,
+    /// Bilinear, then sharpen to recover some of the lost detail
+    Sharpen,
+}
+
+#[derive(Copy, Clone, Default, Debug, Deserialize, Serialize, PartialEq)]
+pub enum Antialiasing {
+    /// Only smooth the edges of lines in the shaders
+    #[default]
+    None,
+    /// Draw lines and endpoints with 4x multisampling
+    Msaa4x,
+    /// Draw at a multiple of the physical resolution and filter down
+    Supersample(f32),
+}
+
+#[derive(Clone, Default, Debug, Deserialize, Serialize, PartialEq)]
+pub enum EndpointStyle {
+    None,
+    #[default]
+    Disc,
+    /// A soft halo that fades out around the tip
+    Glow,
+    Ring,
+    /// A small image, tinted with the line color
+    Sprite(std::path::PathBuf),
+}
+
+impl From<&EndpointStyle> for u32 {
+    fn from(val: &EndpointStyle) -> Self {
+        match val {
+            EndpointStyle::None | EndpointStyle::Disc => 0,
+            EndpointStyle::Glow => 1,
+            EndpointStyle::Ring => 2,
+            EndpointStyle::Sprite(_) => 3,
+        }
+    }
+}
+
+/// Colors are sRGB-encoded, in the range 0.0 to 1.0.
+#[derive(Clone, Default, Debug, Deserialize, Serialize, PartialEq)]
+pub enum Background {
+    #[default]
+    Black,
+    Solid([f32; 3]),
+    VerticalGradient {
+        top: [f32; 3],
+        bottom: [f32; 3],
+    },
+    RadialGradient {
+        center: [f32; 3],
+        edge: [f32; 3],
+    },
+    /// An image, scaled to cover the screen
+    Image(std::path::PathBuf),
+}
+
+impl From<&Background> for u32 {
+    fn from(val: &Background) -> Self {
+        match val {
+            Background::Black | Background::Solid(_) => 0,
+            Background::VerticalGradient { .. } => 1,
+            Background::RadialGradient { .. } => 2,
+            Background::Image(_) => 3,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub enum ColorMapping {
+    /// Pick colors from the palette or image as usual
+    #[default]
+    Palette,
+    /// Go around a hue wheel of the palette with the direction of the flow
+    Angle,
+    /// Ramp through the palette from calm to fast flow
+    Speed,
+}
+
+impl From<ColorMapping> for u32 {
+    fn from(val: ColorMapping) -> Self {
+        match val {
+            ColorMapping::Palette => 0,
+            ColorMapping::Angle => 1,
+            ColorMapping::Speed => 2,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub enum BackgroundBlend {
+    /// Add the light from the lines to the background
+    #[default]
+    Add,
+    /// Like add, but softer over bright backgrounds
+    Screen,
+    /// Keep the brighter of the lines and the background
+    Lighten,
+}
+
+impl From<BackgroundBlend> for u32 {
+    fn from(val: BackgroundBlend) -> Self {
+        match val {
+            BackgroundBlend::Add => 0,
+            BackgroundBlend::Screen => 1,
+            BackgroundBlend::Lighten => 2,
         }
     }
 }
@@ -79,6 +516,10 @@ impl Default for Settings {
 pub enum Mode {
     #[default]
     Normal,
+    /// Particles carried by the fluid, leaving fading trails
+    Particles,
+    /// Ink advected through the fluid and colored from the palette
+    Dye,
     DebugNoise,
     DebugFluid,
     DebugPressure,
@@ -97,10 +538,12 @@ impl Default for PressureMode {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum ColorMode {
     Preset(ColorPreset),
     ImageFile(std::path::PathBuf),
+    Slideshow(Slideshow),
+    AnimatedImageFile(std::path::PathBuf),
 }
 
 impl Default for ColorMode {
@@ -114,7 +557,30 @@ impl From<ColorMode> for u32 {
         match val {
             ColorMode::Preset(ColorPreset::Original) => 0,
             ColorMode::Preset(_) => 1,
-            ColorMode::ImageFile(_) => 2,
+            ColorMode::ImageFile(_) => 3,
+            ColorMode::Slideshow(_) => 3,
+            ColorMode::AnimatedImageFile(_) => 3,
+        }
+    }
+}
+
+/// Cycle through the images in a directory, crossfading between them.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Slideshow {
+    pub directory: std::path::PathBuf,
+    /// Seconds to show each image, including the crossfade.
+    pub interval: f32,
+    /// Seconds to crossfade between consecutive images.
+    pub crossfade: f32,
+}
+
+impl Default for Slideshow {
+    fn default() -> Self {
+        Self {
+            directory: std::path::PathBuf::new(),
+            interval: 300.0,
+            crossfade: 5.0,
         }
     }
 }
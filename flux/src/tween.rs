@@ -0,0 +1,379 @@
+// Eases numeric settings from their old to their new values, so that changes
+// morph the lines instead of snapping to them.
+//
+// Every f32 field of `Settings` is eased, including those of the pressure mode,
+// camera drift, auto-exposure and noise channels when both settings have them,
+// and the background colors when both backgrounds are of the same kind. The
+// rest, and all the other settings, switch to their new values straight away:
+//
+// - `render_scale` and `antialiasing` reallocate the offscreen scene.
+// - `fade_duration`, `tween_duration` and the slideshow's `interval` and
+//   `crossfade` time the animations themselves.
+// - `layers` and `line_density` lay the lines out again, which resets them.
+//
+// While easing, only the uniforms are updated. The GPU resources are set up
+// once when the tween starts, and again when it finishes.
+
+use crate::settings::{Background, PressureMode, Settings};
+
+use std::sync::Arc;
+
+pub struct Tween {
+    from: Settings,
+    to: Arc<Settings>,
+    duration: f32,
+    // Seconds since the tween started
+    elapsed: f32,
+}
+
+// Assign each field, eased between the two settings
+macro_rules! lerp_fields {
+    ($settings:ident, $from:expr, $to:expr, $t:expr, [$($field:ident),* $(,)?]) => {
+        $( $settings.$field = $from.$field + ($to.$field - $from.$field) * $t; )*
+    };
+}
+
+impl Tween {
+    /// Start easing from the current settings to the new ones over the new
+    /// tween duration. To retarget a tween in progress, start a new one from
+    /// its current settings.
+    pub fn new(from: &Settings, to: &Arc<Settings>) -> Self {
+        Self {
+            from: from.clone(),
+            to: Arc::clone(to),
+            duration: to.tween_duration.max(0.0),
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn tick(&mut self, timestep: f32) {
+        self.elapsed = (self.elapsed + timestep).min(self.duration);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// The settings being eased towards.
+    pub fn get_target(&self) -> &Arc<Settings> {
+        &self.to
+    }
+
+    /// The settings to set up the GPU resources with when the tween starts.
+    /// Effects switched on at either end are set up, so that easing them in
+    /// or out only has to update their uniforms.
+    pub fn get_setup_settings(&self) -> Settings {
+        let mut settings = self.get_settings();
+        settings.bloom_intensity = self.from.bloom_intensity.max(self.to.bloom_intensity);
+        settings.trail_decay = self.from.trail_decay.max(self.to.trail_decay);
+        settings.film_grain = self.from.film_grain.max(self.to.film_grain);
+        settings.vignette = self.from.vignette.max(self.to.vignette);
+        settings.safe_zone_dimming = self.from.safe_zone_dimming.max(self.to.safe_zone_dimming);
+        settings
+    }
+
+    /// The new settings, with the numeric fields part way there.
+    pub fn get_settings(&self) -> Settings {
+        let progress = if self.duration > 0.0 {
+            (self.elapsed / self.duration).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        // Ease in and out
+        let t = progress * progress * (3.0 - 2.0 * progress);
+
+        let mut settings = (*self.to).clone();
+        lerp_fields!(
+            settings,
+            self.from,
+            self.to,
+            t,
+            [
+                fluid_frame_rate,
+                fluid_timestep,
+                viscosity,
+                velocity_dissipation,
+                line_length,
+                line_width,
+                line_begin_offset,
+                line_variance,
+                line_curvature,
+                line_taper,
+                endpoint_size,
+                endpoint_opacity,
+                view_scale,
+                view_rotation,
+                rotation_speed,
+                particle_size,
+                particle_lifetime,
+                flow_speed,
+                dye_dissipation,
+                dye_injection,
+                noise_multiplier,
+                brightness_multiplier,
+                max_saturation,
+                max_lightness,
+                base_intensity,
+                exposure,
+                hdr_headroom,
+                bloom_intensity,
+                bloom_radius,
+                trail_decay,
+                film_grain,
+                vignette,
+                safe_zone_dimming,
+            ]
+        );
+
+        if let (
+            PressureMode::ClearWith(pressure),
+            PressureMode::ClearWith(from),
+            PressureMode::ClearWith(to),
+        ) = (
+            &mut settings.pressure_mode,
+            self.from.pressure_mode,
+            self.to.pressure_mode,
+        ) {
+            *pressure = from + (to - from) * t;
+        }
+
+        if let (Some(drift), Some(from), Some(to)) = (
+            &mut settings.camera_drift,
+            &self.from.camera_drift,
+            &self.to.camera_drift,
+        ) {
+            lerp_fields!(drift, from, to, t, [speed, range]);
+        }
+
+        if let (Some(auto_exposure), Some(from), Some(to)) = (
+            &mut settings.auto_exposure,
+            &self.from.auto_exposure,
+            &self.to.auto_exposure,
+        ) {
+            lerp_fields!(
+                auto_exposure,
+                from,
+                to,
+                t,
+                [
+                    target_luminance,
+                    highlight_percentile,
+                    adaptation_rate,
+                    min_exposure,
+                    max_exposure,
+                ]
+            );
+        }
+
+        match (&mut settings.background, &self.from.background) {
+            (Background::Solid(color), Background::Solid(from)) => {
+                *color = lerp_color(*from, *color, t);
+            }
+            (
+                Background::VerticalGradient { top, bottom },
+                Background::VerticalGradient {
+                    top: from_top,
+                    bottom: from_bottom,
+                },
+            ) => {
+                *top = lerp_color(*from_top, *top, t);
+                *bottom = lerp_color(*from_bottom, *bottom, t);
+            }
+            (
+                Background::RadialGradient { center, edge },
+                Background::RadialGradient {
+                    center: from_center,
+                    edge: from_edge,
+                },
+            ) => {
+                *center = lerp_color(*from_center, *center, t);
+                *edge = lerp_color(*from_edge, *edge, t);
+            }
+            _ => {}
+        }
+
+        if self.from.noise_channels.len() == self.to.noise_channels.len() {
+            let channels = self.from.noise_channels.iter().zip(&self.to.noise_channels);
+            for (noise, (from, to)) in settings.noise_channels.iter_mut().zip(channels) {
+                lerp_fields!(noise, from, to, t, [scale, multiplier, offset_increment]);
+            }
+        }
+
+        settings
+    }
+}
+
+// Ease the sRGB-encoded components, like the color pickers do
+fn lerp_color(from: [f32; 3], to: [f32; 3], t: f32) -> [f32; 3] {
+    std::array::from_fn(|i| from[i] + (to[i] - from[i]) * t)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::{Antialiasing, ColorMode, LineDensity, Mode, Slideshow};
+    use serde_json::Value;
+
+    // The numeric settings that switch straight away, by their path in the
+    // serialized settings
+    const SWITCHED: [&str; 13] = [
+        "renderScale",
+        "antialiasing.Supersample",
+        "fadeDuration",
+        "tweenDuration",
+        "colorMode.Slideshow.interval",
+        "colorMode.Slideshow.crossfade",
+        "layers.lineLength",
+        "layers.lineWidth",
+        "layers.opacity",
+        "layers.viewScale",
+        "layers.depth",
+        "lineDensity.Radial.center",
+        "lineDensity.Radial.edge",
+    ];
+
+    fn get_target() -> Arc<Settings> {
+        Arc::new(Settings {
+            mode: Mode::Particles,
+            line_length: 2.0 * Settings::default().line_length,
+            render_scale: 0.5,
+            tween_duration: 1.0,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn eases_numeric_settings() {
+        let from = Settings::default();
+        let to = get_target();
+        let mut tween = Tween::new(&from, &to);
+        assert_eq!(tween.get_settings().line_length, from.line_length);
+
+        tween.tick(0.5);
+        let halfway = 0.5 * (from.line_length + to.line_length);
+        assert!((tween.get_settings().line_length - halfway).abs() < 1e-6);
+
+        tween.tick(0.5);
+        assert!(tween.is_finished());
+        assert_eq!(tween.get_settings().line_length, to.line_length);
+    }
+
+    #[test]
+    fn switches_other_settings_straight_away() {
+        let tween = Tween::new(&Settings::default(), &get_target());
+        let settings = tween.get_settings();
+        assert_eq!(settings.mode, Mode::Particles);
+        assert_eq!(settings.render_scale, 0.5);
+    }
+
+    #[test]
+    fn sets_up_effects_at_either_end() {
+        let from = Settings {
+            bloom_intensity: 0.5,
+            ..Default::default()
+        };
+        let to = Arc::new(Settings {
+            bloom_intensity: 0.0,
+            vignette: 0.4,
+            tween_duration: 1.0,
+            ..Default::default()
+        });
+        let settings = Tween::new(&from, &to).get_setup_settings();
+        assert_eq!(settings.bloom_intensity, 0.5);
+        assert_eq!(settings.vignette, 0.4);
+    }
+
+    #[test]
+    fn eases_or_switches_every_numeric_setting() {
+        let gray = [0.5; 3];
+        let backgrounds = [
+            Background::Solid(gray),
+            Background::VerticalGradient {
+                top: gray,
+                bottom: gray,
+            },
+            Background::RadialGradient {
+                center: gray,
+                edge: gray,
+            },
+        ];
+
+        for background in backgrounds {
+            let from = Settings {
+                color_mode: ColorMode::Slideshow(Slideshow::default()),
+                antialiasing: Antialiasing::Supersample(2.0),
+                background,
+                camera_drift: Some(Default::default()),
+                auto_exposure: Some(Default::default()),
+                layers: vec![Default::default()],
+                line_density: LineDensity::Radial {
+                    center: 1.0,
+                    edge: 0.5,
+                },
+                ..Default::default()
+            };
+            let mut value = serde_json::to_value(&from).unwrap();
+            nudge_floats(&mut value);
+            let to: Arc<Settings> = Arc::new(serde_json::from_value(value).unwrap());
+
+            let mut tween = Tween::new(&from, &to);
+            tween.tick(0.5 * to.tween_duration);
+
+            let targets = get_floats(&to);
+            let halfway = get_floats(&tween.get_settings());
+            for path in SWITCHED {
+                assert!(
+                    targets.iter().any(|(target_path, _)| target_path == path),
+                    "{path} is not a numeric setting"
+                );
+            }
+            assert_eq!(targets.len(), halfway.len());
+            for ((path, target), (_, value)) in targets.iter().zip(&halfway) {
+                assert!(
+                    value != target || SWITCHED.contains(&path.as_str()),
+                    "{path} is neither eased nor listed as switching straight away"
+                );
+            }
+        }
+    }
+
+    fn nudge_floats(value: &mut Value) {
+        match value {
+            Value::Number(number) if number.is_f64() => {
+                *value = (number.as_f64().unwrap() + 1.0).into();
+            }
+            Value::Array(values) => values.iter_mut().for_each(nudge_floats),
+            Value::Object(fields) => fields.values_mut().for_each(nudge_floats),
+            _ => {}
+        }
+    }
+
+    // Every float in the serialized settings, by its path
+    fn get_floats(settings: &Settings) -> Vec<(String, f64)> {
+        fn collect(value: &Value, path: &str, floats: &mut Vec<(String, f64)>) {
+            match value {
+                Value::Number(number) if number.is_f64() => {
+                    floats.push((path.to_string(), number.as_f64().unwrap()));
+                }
+                Value::Array(values) => {
+                    values.iter().for_each(|value| collect(value, path, floats))
+                }
+                Value::Object(fields) => {
+                    for (key, value) in fields {
+                        let path = if path.is_empty() {
+                            key.clone()
+                        } else {
+                            format!("{path}.{key}")
+                        };
+                        collect(value, &path, floats);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut floats = Vec::new();
+        collect(&serde_json::to_value(settings).unwrap(), "", &mut floats);
+        floats
+    }
+}